@@ -0,0 +1,54 @@
+//! Decode and encode throughput at several resolutions and endiannesses.
+//!
+//! Run with `cargo bench`. Criterion is not available, so each case reports
+//! the median of a fixed number of timed iterations.
+use pxm::{Endian, PfmWriteOptions, PFM};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: usize = 15;
+
+const RESOLUTIONS: [(usize, usize); 3] = [(640, 480), (1920, 1080), (3840, 2160)];
+
+fn main() {
+    for &(width, height) in RESOLUTIONS.iter() {
+        let pfm = PFM::from_fn(width, height, true, |x, y, c| (x + y + c) as f32);
+        for &endian in [Endian::Little, Endian::Big].iter() {
+            let mut bytes = Vec::new();
+            pfm.write_into_with(&mut bytes, &mut PfmWriteOptions::new().endian(endian))
+                .unwrap();
+            let name = format!("{}x{} {:?}", width, height, endian);
+
+            report("decode", &name, bytes.len(), || {
+                black_box(PFM::from_bytes(black_box(&bytes)).unwrap());
+            });
+            report("encode", &name, bytes.len(), || {
+                let mut buffer = Vec::with_capacity(bytes.len());
+                pfm.write_into_with(&mut buffer, &mut PfmWriteOptions::new().endian(endian))
+                    .unwrap();
+                black_box(buffer);
+            });
+        }
+    }
+}
+
+fn report(op: &str, name: &str, bytes: usize, mut f: impl FnMut()) {
+    f();
+    let mut times: Vec<Duration> = (0..ITERATIONS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+
+    let median = times[ITERATIONS / 2];
+    println!(
+        "{} {:<16} {:>10.3} ms {:>8.1} MB/s",
+        op,
+        name,
+        median.as_secs_f64() * 1e3,
+        bytes as f64 / median.as_secs_f64() / 1e6
+    );
+}
@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use pxm::PFM;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = PFM::from_bytes(data);
+});
@@ -0,0 +1,368 @@
+use crate::pfm::PFM;
+use std::cmp::Ordering;
+
+/// Method used by `PFM::normalize` to find the value range to map to [0, 1].
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Normalization {
+    /// Use the minimum and maximum value.
+    MinMax,
+    /// Use the `low` and `high` percentiles (in [0, 100]), clamping the
+    /// values outside.
+    Percentile { low: f32, high: f32 },
+}
+
+impl PFM {
+    /// Rescale the pixel values to [0, 1] over all channels and reset the
+    /// scaling factor to 1, so that `scaled_data` is normalized as well. NaN
+    /// and infinite values are ignored when finding the value range and left
+    /// untouched.
+    pub fn normalize(&mut self, method: Normalization) -> Result<(), &'static str> {
+        let mut values: Vec<f32> = self
+            .data
+            .iter()
+            .copied()
+            .filter(|v| v.is_finite())
+            .collect();
+        if values.is_empty() {
+            return Err("The image has no finite values");
+        }
+
+        let (low, high) = match method {
+            Normalization::MinMax => values
+                .iter()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &v| {
+                    (lo.min(v), hi.max(v))
+                }),
+            Normalization::Percentile { low, high } => {
+                if !(0.0..=100.0).contains(&low) || !(0.0..=100.0).contains(&high) || low > high {
+                    return Err("Percentiles must be in [0, 100] and low must not exceed high");
+                }
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                (percentile(&values, low), percentile(&values, high))
+            }
+        };
+
+        let range = high - low;
+        for v in self.data.iter_mut().filter(|v| v.is_finite()) {
+            *v = if range > 0.0 {
+                ((*v - low) / range).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+        }
+        self.scale_factor = 1.0;
+
+        Ok(())
+    }
+
+    /// Get the physical pixel values, i.e. `data` multiplied by the scaling
+    /// factor. `data` itself holds the raw values.
+    pub fn scaled_data(&self) -> Vec<f32> {
+        self.data.iter().map(|v| v * self.scale_factor).collect()
+    }
+
+    /// Multiply `data` by the scaling factor and reset it to 1, so that `data`
+    /// holds the physical values.
+    pub fn apply_scale(&mut self) {
+        for v in self.data.iter_mut() {
+            *v *= self.scale_factor;
+        }
+        self.scale_factor = 1.0;
+    }
+
+    /// Divide `data` by `scale` and multiply the scaling factor by it, so
+    /// that the physical values are unchanged. This is the inverse of
+    /// `apply_scale` when the scaling factor is 1.
+    pub fn factor_out_scale(&mut self, scale: f32) -> Result<(), &'static str> {
+        if scale <= 0.0 || !scale.is_finite() {
+            return Err("Invalid scaling factor");
+        }
+
+        for v in self.data.iter_mut() {
+            *v /= scale;
+        }
+        self.scale_factor *= scale;
+
+        Ok(())
+    }
+
+    /// Change the scaling factor to `factor`, adjusting `data` so that the
+    /// physical values are unchanged.
+    pub fn rescale_to(&mut self, factor: f32) -> Result<(), &'static str> {
+        if factor <= 0.0 || !factor.is_finite() {
+            return Err("Invalid scaling factor");
+        }
+
+        let ratio = self.scale_factor / factor;
+        for v in self.data.iter_mut() {
+            *v *= ratio;
+        }
+        self.scale_factor = factor;
+
+        Ok(())
+    }
+
+    /// Add the physical values of `other` to a copy of the image. `other` is
+    /// rescaled to the scaling factor of the image first.
+    pub fn add(&self, other: &PFM) -> Result<PFM, &'static str> {
+        self.combine(other, |a, b| a + b)
+    }
+
+    /// Subtract the physical values of `other` from a copy of the image.
+    /// `other` is rescaled to the scaling factor of the image first.
+    pub fn diff(&self, other: &PFM) -> Result<PFM, &'static str> {
+        self.combine(other, |a, b| a - b)
+    }
+
+    /// Linearly interpolate the physical values from the image (`t` = 0) to
+    /// `other` (`t` = 1). `other` is rescaled to the scaling factor of the
+    /// image first.
+    pub fn blend(&self, other: &PFM, t: f32) -> Result<PFM, &'static str> {
+        self.combine(other, |a, b| a + (b - a) * t)
+    }
+
+    fn combine(&self, other: &PFM, f: impl Fn(f32, f32) -> f32) -> Result<PFM, &'static str> {
+        if self.width != other.width || self.height != other.height || self.color != other.color {
+            return Err("The images have different sizes or channels");
+        }
+
+        if self.row_order != other.row_order {
+            return Err("The images have different row orders");
+        }
+
+        let ratio = other.scale_factor / self.scale_factor;
+        let mut pfm = self.clone();
+        for (a, &b) in pfm.data.iter_mut().zip(other.data.iter()) {
+            *a = f(*a, b * ratio);
+        }
+
+        Ok(pfm)
+    }
+
+    /// Replace each value `v` of all channels by `f(v)` in place.
+    pub fn map_pixels(&mut self, mut f: impl FnMut(f32) -> f32) {
+        for v in self.data.iter_mut() {
+            *v = f(*v);
+        }
+    }
+
+    /// Get a copy of the image with each value `v` replaced by `f(v)`.
+    pub fn mapped_pixels(&self, f: impl FnMut(f32) -> f32) -> PFM {
+        let mut pfm = self.clone();
+        pfm.map_pixels(f);
+        pfm
+    }
+
+    /// Call `f(x, y, pixel)` on the channel values of each pixel in place.
+    pub fn map_pixels_with_coords(&mut self, mut f: impl FnMut(usize, usize, &mut [f32])) {
+        for (x, y, pixel) in self.pixels_mut() {
+            f(x, y, pixel);
+        }
+    }
+
+    /// Get a copy of the image with `f(x, y, pixel)` called on the channel
+    /// values of each pixel.
+    pub fn mapped_pixels_with_coords(&self, f: impl FnMut(usize, usize, &mut [f32])) -> PFM {
+        let mut pfm = self.clone();
+        pfm.map_pixels_with_coords(f);
+        pfm
+    }
+
+    /// Replace NaN and infinite values of all channels by `value`, returning
+    /// the number of replaced values.
+    pub fn replace_non_finite(&mut self, value: f32) -> usize {
+        replace_non_finite(self.data.iter_mut(), value)
+    }
+
+    /// Replace NaN and infinite values of `channel` by `value`, returning the
+    /// number of replaced values.
+    pub fn replace_non_finite_channel(
+        &mut self,
+        channel: usize,
+        value: f32,
+    ) -> Result<usize, &'static str> {
+        let num_channels = self.num_channels();
+        if channel >= num_channels {
+            return Err("The channel does not exist");
+        }
+
+        Ok(replace_non_finite(
+            self.data.iter_mut().skip(channel).step_by(num_channels),
+            value,
+        ))
+    }
+
+    /// Clamp the values of all channels to [`min`, `max`]. NaN values are left
+    /// untouched.
+    pub fn clamp(&mut self, min: f32, max: f32) -> Result<(), &'static str> {
+        let channels: Vec<usize> = (0..self.num_channels()).collect();
+        self.clamp_channels(&channels, min, max)
+    }
+
+    /// Clamp the values of the given `channels` to [`min`, `max`]. NaN values
+    /// are left untouched.
+    pub fn clamp_channels(
+        &mut self,
+        channels: &[usize],
+        min: f32,
+        max: f32,
+    ) -> Result<(), &'static str> {
+        if min.is_nan() || max.is_nan() || min > max {
+            return Err("Invalid clamping range");
+        }
+
+        let num_channels = self.num_channels();
+        if channels.iter().any(|&c| c >= num_channels) {
+            return Err("The channel does not exist");
+        }
+
+        for pixel in self.data.chunks_exact_mut(num_channels) {
+            for &c in channels {
+                pixel[c] = pixel[c].clamp(min, max);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn replace_non_finite<'a>(values: impl Iterator<Item = &'a mut f32>, value: f32) -> usize {
+    let mut count = 0;
+    for v in values.filter(|v| !v.is_finite()) {
+        *v = value;
+        count += 1;
+    }
+
+    count
+}
+
+/// Linearly interpolated percentile `p` of sorted `values`.
+fn percentile(values: &[f32], p: f32) -> f32 {
+    let pos = p / 100.0 * (values.len() - 1) as f32;
+    let lower = pos.floor() as usize;
+    let upper = pos.ceil() as usize;
+    let t = pos - lower as f32;
+
+    values[lower] * (1.0 - t) + values[upper] * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::{mono, PFMBuilder};
+
+    #[test]
+    fn test_normalize() {
+        let mut pfm = mono(4, 1, vec![2.0, 4.0, f32::NAN, 6.0]);
+        pfm.scale_factor = 3.0;
+        pfm.normalize(Normalization::MinMax).unwrap();
+        assert_eq!(pfm.scale_factor, 1.0);
+        assert_eq!(pfm.data[..2], [0.0, 0.5]);
+        assert!(pfm.data[2].is_nan());
+        assert_eq!(pfm.data[3], 1.0);
+
+        let mut pfm = mono(101, 1, (0..=100).map(|v| v as f32).collect());
+        pfm.data[100] = 1000.0;
+        pfm.normalize(Normalization::Percentile {
+            low: 10.0,
+            high: 90.0,
+        })
+        .unwrap();
+        assert_eq!(pfm.data[0], 0.0);
+        assert_eq!(pfm.data[50], 0.5);
+        assert_eq!(pfm.data[100], 1.0);
+
+        let mut pfm = mono(1, 1, vec![f32::NAN]);
+        assert!(pfm.normalize(Normalization::MinMax).is_err());
+    }
+
+    #[test]
+    fn test_scale() {
+        let mut pfm = mono(2, 1, vec![1.0, -2.0]);
+        pfm.scale_factor = 4.0;
+        assert_eq!(pfm.scaled_data(), vec![4.0, -8.0]);
+
+        pfm.apply_scale();
+        assert_eq!(pfm.scale_factor, 1.0);
+        assert_eq!(pfm.data, vec![4.0, -8.0]);
+
+        pfm.factor_out_scale(4.0).unwrap();
+        assert_eq!(pfm.scale_factor, 4.0);
+        assert_eq!(pfm.data, vec![1.0, -2.0]);
+        assert!(pfm.factor_out_scale(0.0).is_err());
+        assert!(pfm.factor_out_scale(-2.0).is_err());
+    }
+
+    #[test]
+    fn test_combine() {
+        let mut a = mono(2, 1, vec![1.0, 2.0]);
+        a.scale_factor = 2.0;
+        let mut b = mono(2, 1, vec![4.0, 8.0]);
+        b.scale_factor = 0.5;
+
+        let sum = a.add(&b).unwrap();
+        assert_eq!(sum.scale_factor, 2.0);
+        assert_eq!(sum.scaled_data(), vec![4.0, 8.0]);
+        assert_eq!(a.diff(&b).unwrap().scaled_data(), vec![0.0, 0.0]);
+        assert_eq!(
+            a.blend(&mono(2, 1, vec![0.0, 0.0]), 0.5).unwrap().data,
+            vec![0.5, 1.0]
+        );
+        assert!(a.add(&mono(1, 1, vec![1.0])).is_err());
+
+        b.rescale_to(2.0).unwrap();
+        assert_eq!(b.data, vec![1.0, 2.0]);
+        assert!(b.rescale_to(-1.0).is_err());
+    }
+
+    #[test]
+    fn test_map_pixels() {
+        let mut pfm = mono(2, 2, vec![1.0, 2.0, 3.0, 4.0]);
+
+        assert_eq!(
+            pfm.mapped_pixels(|v| v * 2.0).data,
+            vec![2.0, 4.0, 6.0, 8.0]
+        );
+
+        pfm.map_pixels_with_coords(|x, y, pixel| pixel[0] += (x * 10 + y * 100) as f32);
+        assert_eq!(pfm.data, vec![1.0, 12.0, 103.0, 114.0]);
+    }
+
+    #[test]
+    fn test_replace_non_finite() {
+        let mut pfm = PFMBuilder::new()
+            .color(true)
+            .size(2, 1)
+            .data(vec![f32::NAN, 1.0, 2.0, f32::INFINITY, f32::NAN, 5.0])
+            .build()
+            .unwrap();
+
+        assert_eq!(pfm.replace_non_finite_channel(1, -1.0), Ok(1));
+        assert_eq!(pfm.data[4], -1.0);
+        assert!(pfm.replace_non_finite_channel(3, 0.0).is_err());
+
+        assert_eq!(pfm.replace_non_finite(0.0), 2);
+        assert_eq!(pfm.data, vec![0.0, 1.0, 2.0, 0.0, -1.0, 5.0]);
+    }
+
+    #[test]
+    fn test_clamp() {
+        let mut pfm = PFMBuilder::new()
+            .color(true)
+            .size(1, 2)
+            .data(vec![-1.0, 0.5, 2.0, f32::NAN, 3.0, -4.0])
+            .build()
+            .unwrap();
+
+        pfm.clamp_channels(&[2], 0.0, 1.0).unwrap();
+        assert_eq!(&pfm.data[..3], &[-1.0, 0.5, 1.0]);
+        assert_eq!(pfm.data[5], 0.0);
+        assert!(pfm.clamp_channels(&[3], 0.0, 1.0).is_err());
+
+        pfm.clamp(0.0, 1.0).unwrap();
+        assert_eq!(&pfm.data[..3], &[0.0, 0.5, 1.0]);
+        assert!(pfm.data[3].is_nan());
+        assert_eq!(&pfm.data[4..], &[1.0, 0.0]);
+        assert!(pfm.clamp(1.0, 0.0).is_err());
+    }
+}
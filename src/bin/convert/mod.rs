@@ -0,0 +1,120 @@
+//! Conversion shared by `pxm-convert` and `pxm-batch`.
+use pxm::{Endian, PfmWriteOptions, ToneMap, PFM, PXM};
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Usage of the options parsed by `Conversion::parse_option`.
+pub const OPTIONS_USAGE: &str = "[--endian little|big|native] [--gamma G | --reinhard KEY]";
+
+/// Supported output formats, chosen by the output file extension.
+pub const FORMATS: [&str; 3] = ["pfm", "pgm", "ppm"];
+
+/// Endianness and tone mapping of the output. PGM and PPM outputs are tone
+/// mapped with a gamma of 2.2 by default.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Conversion {
+    pub endian: Option<Endian>,
+    pub tonemap: Option<ToneMap>,
+}
+
+impl Conversion {
+    /// Parse `arg`, taking its value from `args`. Returns false if `arg` is
+    /// not a conversion option.
+    pub fn parse_option(
+        &mut self,
+        arg: &str,
+        args: &mut impl Iterator<Item = String>,
+    ) -> Result<bool, &'static str> {
+        match arg {
+            "--endian" => {
+                self.endian = match args.next().as_deref() {
+                    Some("little") => Some(Endian::Little),
+                    Some("big") => Some(Endian::Big),
+                    Some("native") => Some(Endian::Native),
+                    _ => return Err("--endian must be little, big or native"),
+                }
+            }
+            "--gamma" => self.tonemap = Some(ToneMap::Gamma(parse_value(args.next())?)),
+            "--reinhard" => {
+                self.tonemap = Some(ToneMap::Reinhard {
+                    key: parse_value(args.next())?,
+                })
+            }
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Convert the PFM file `input` to `output`.
+    pub fn run(&self, input: &Path, output: &Path) -> Result<(), &'static str> {
+        let PXM::PFM(pfm) = PXM::load(input)?;
+
+        let ext = output
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        let ext = match ext {
+            Some(ext) if FORMATS.contains(&ext.as_str()) => ext,
+            _ => return Err("Unsupported output extension"),
+        };
+
+        let file = match File::create(output) {
+            Ok(file) => file,
+            Err(_) => return Err("Unable to create the output file"),
+        };
+        let mut writer = BufWriter::new(file);
+
+        match ext.as_str() {
+            "pfm" => {
+                let pfm = match self.tonemap {
+                    Some(op) => pfm.tonemap(op)?,
+                    None => pfm,
+                };
+                let mut options = PfmWriteOptions::new();
+                if let Some(endian) = self.endian {
+                    options = options.endian(endian);
+                }
+                pfm.write_into_with(&mut writer, &mut options)
+            }
+            "pgm" => {
+                let pfm = if pfm.color {
+                    pfm.to_grayscale([0.2126, 0.7152, 0.0722])?
+                } else {
+                    pfm
+                };
+                write_8bit(&mut writer, &self.tonemap_8bit(&pfm)?)
+            }
+            _ => {
+                let pfm = if pfm.color { pfm } else { pfm.to_color()? };
+                write_8bit(&mut writer, &self.tonemap_8bit(&pfm)?)
+            }
+        }
+    }
+
+    fn tonemap_8bit(&self, pfm: &PFM) -> Result<PFM, &'static str> {
+        pfm.tonemap(self.tonemap.unwrap_or(ToneMap::Gamma(2.2)))
+    }
+}
+
+fn parse_value(arg: Option<String>) -> Result<f32, &'static str> {
+    match arg.map(|arg| arg.parse()) {
+        Some(Ok(v)) => Ok(v),
+        _ => Err("Expected a number"),
+    }
+}
+
+/// Write a tone mapped image as a binary PGM (P5) or PPM (P6) file. PFM rows
+/// are already stored top to bottom in memory, as these formats expect.
+fn write_8bit(writer: &mut impl Write, pfm: &PFM) -> Result<(), &'static str> {
+    let magic = if pfm.color { "P6" } else { "P5" };
+    let mut bytes = format!("{}\n{} {}\n255\n", magic, pfm.width, pfm.height).into_bytes();
+    bytes.extend(pfm.data.iter().map(|v| (v * 255.0).round() as u8));
+
+    match writer.write_all(&bytes).and_then(|_| writer.flush()) {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Unable to write the output file"),
+    }
+}
@@ -0,0 +1,152 @@
+//! Convert many PFM files in parallel, writing `OUT_DIR/<file stem>.FORMAT`
+//! for every input file, while showing the progress on stderr.
+//!
+//! Usage: pxm-batch [--jobs N] [--endian little|big|native] [--gamma G | --reinhard KEY] OUT_DIR FORMAT FILE...
+//!
+//! FORMAT is pfm, pgm or ppm. Pass a shell glob such as `data/*.pfm` to
+//! convert a whole directory. The number of jobs defaults to the number of
+//! CPUs. Inputs that would be written to the same output file, such as
+//! `left/0001.pfm` and `right/0001.pfm`, are rejected before converting.
+mod convert;
+
+use convert::{Conversion, FORMATS, OPTIONS_USAGE};
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+const PROGRESS_WIDTH: usize = 40;
+
+fn main() {
+    let mut conversion = Conversion::default();
+    let mut jobs = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut positional = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--jobs" {
+            jobs = match args.next().map(|arg| arg.parse()) {
+                Some(Ok(jobs)) if jobs > 0 => jobs,
+                _ => exit_with_usage("--jobs expects a positive number"),
+            };
+            continue;
+        }
+
+        match conversion.parse_option(&arg, &mut args) {
+            Ok(true) => {}
+            Ok(false) => positional.push(arg),
+            Err(e) => exit_with_usage(e),
+        }
+    }
+
+    if positional.len() < 3 {
+        exit_with_usage("Expected an output directory, a format and input files");
+    }
+
+    let out_dir = PathBuf::from(&positional[0]);
+    let format = positional[1].to_lowercase();
+    if !FORMATS.contains(&format.as_str()) {
+        exit_with_usage("FORMAT must be pfm, pgm or ppm");
+    }
+    if !out_dir.is_dir() {
+        eprintln!("{}: Not a directory", out_dir.display());
+        process::exit(2);
+    }
+
+    let inputs = &positional[2..];
+    let outputs: Vec<PathBuf> = inputs
+        .iter()
+        .map(|input| output_path(input, &out_dir, &format))
+        .collect();
+    let mut sources = HashMap::new();
+    for (input, output) in inputs.iter().zip(outputs.iter()) {
+        if let Some(other) = sources.insert(output, input) {
+            eprintln!(
+                "{} and {} would both be written to {}",
+                other,
+                input,
+                output.display()
+            );
+            process::exit(2);
+        }
+    }
+
+    let failures = convert_all(&conversion, inputs, &outputs, jobs);
+    for (path, e) in &failures {
+        eprintln!("{}: {}", path, e);
+    }
+
+    if !failures.is_empty() {
+        eprintln!("{} of {} files failed", failures.len(), inputs.len());
+        process::exit(1);
+    }
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!(
+        "{}\nUsage: pxm-batch [--jobs N] {} OUT_DIR FORMAT FILE...",
+        message, OPTIONS_USAGE
+    );
+    process::exit(2);
+}
+
+/// Convert `inputs` into the corresponding `outputs` on `jobs` threads, which
+/// take the next file from a shared index. Returns the failed files with their
+/// errors.
+fn convert_all<'a>(
+    conversion: &Conversion,
+    inputs: &'a [String],
+    outputs: &[PathBuf],
+    jobs: usize,
+) -> Vec<(&'a str, &'static str)> {
+    let next = AtomicUsize::new(0);
+    let done = Mutex::new(0);
+    let failures = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(inputs.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = inputs.get(i) else {
+                    break;
+                };
+                if let Err(e) = conversion.run(Path::new(input), &outputs[i]) {
+                    failures.lock().unwrap().push((input.as_str(), e));
+                }
+
+                let mut done = done.lock().unwrap();
+                *done += 1;
+                print_progress(*done, inputs.len());
+            });
+        }
+    });
+
+    eprintln!();
+    failures.into_inner().unwrap()
+}
+
+fn output_path(input: &str, out_dir: &Path, format: &str) -> PathBuf {
+    let stem = Path::new(input).file_stem().unwrap_or_default();
+    let mut output = out_dir.join(stem);
+    output.set_extension(format);
+    output
+}
+
+fn print_progress(done: usize, total: usize) {
+    let filled = done * PROGRESS_WIDTH / total;
+    let mut stderr = io::stderr();
+    let _ = write!(
+        stderr,
+        "\r[{}{}] {}/{}",
+        "#".repeat(filled),
+        "-".repeat(PROGRESS_WIDTH - filled),
+        done,
+        total
+    );
+    let _ = stderr.flush();
+}
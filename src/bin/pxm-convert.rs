@@ -0,0 +1,41 @@
+//! Convert a PFM file into a PFM file of another endianness, or tone map it
+//! into an 8-bit PGM or PPM file.
+//!
+//! Usage: pxm-convert [--endian little|big|native] [--gamma G | --reinhard KEY] IN OUT
+//!
+//! The output format is chosen by the extension of OUT (pfm, pgm or ppm).
+//! PGM and PPM outputs are tone mapped with a gamma of 2.2 by default.
+mod convert;
+
+use convert::{Conversion, OPTIONS_USAGE};
+use std::env;
+use std::path::Path;
+use std::process;
+
+fn main() {
+    let mut conversion = Conversion::default();
+    let mut paths = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match conversion.parse_option(&arg, &mut args) {
+            Ok(true) => {}
+            Ok(false) => paths.push(arg),
+            Err(e) => exit_with_usage(e),
+        }
+    }
+
+    if paths.len() != 2 {
+        exit_with_usage("Expected an input and an output path");
+    }
+
+    if let Err(e) = conversion.run(Path::new(&paths[0]), Path::new(&paths[1])) {
+        eprintln!("{}: {}", paths[0], e);
+        process::exit(1);
+    }
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\nUsage: pxm-convert {} IN OUT", message, OPTIONS_USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,117 @@
+//! Compare the scaled values of two PFM files, printing the maximum and mean
+//! absolute error and the number of differing pixels.
+//!
+//! Usage: pxm-diff [--threshold T] A B
+//!
+//! A pixel differs if any of its channels differs by more than T, which
+//! defaults to 0. Exits with 1 if any pixel differs, and with 2 if the files
+//! can't be compared.
+use pxm::{PFM, PXM};
+use std::env;
+use std::process;
+
+const USAGE: &str = "Usage: pxm-diff [--threshold T] A B";
+
+struct Diff {
+    max_error: f32,
+    mean_error: f64,
+    differing_pixels: usize,
+}
+
+fn main() {
+    let mut threshold = 0.0;
+    let mut paths = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--threshold" {
+            threshold = match args.next().map(|arg| arg.parse()) {
+                Some(Ok(v)) => v,
+                _ => exit_with_usage("--threshold expects a number"),
+            };
+        } else {
+            paths.push(arg);
+        }
+    }
+
+    if paths.len() != 2 {
+        exit_with_usage("Expected two paths");
+    }
+
+    let diff = match load(&paths[0])
+        .and_then(|a| load(&paths[1]).and_then(|b| compare(&a, &b, threshold)))
+    {
+        Ok(diff) => diff,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+
+    println!("max abs error:    {}", diff.max_error);
+    println!("mean abs error:   {}", diff.mean_error);
+    println!("differing pixels: {}", diff.differing_pixels);
+
+    if diff.differing_pixels > 0 {
+        process::exit(1);
+    }
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn load(path: &str) -> Result<PFM, String> {
+    match PXM::load(path) {
+        Ok(PXM::PFM(pfm)) => Ok(pfm),
+        Err(e) => Err(format!("{}: {}", path, e)),
+    }
+}
+
+/// Compare the scaled values of `a` and `b`. Two NaNs are equal, while a NaN
+/// and a number have an infinite error.
+fn compare(a: &PFM, b: &PFM, threshold: f32) -> Result<Diff, String> {
+    if (a.width, a.height, a.color) != (b.width, b.height, b.color) {
+        return Err(format!(
+            "The images differ in shape: {}x{} {} vs {}x{} {}",
+            a.width,
+            a.height,
+            if a.color { "RGB" } else { "mono" },
+            b.width,
+            b.height,
+            if b.color { "RGB" } else { "mono" },
+        ));
+    }
+
+    let num_channels = if a.color { 3 } else { 1 };
+    let (left, right) = (a.scaled_data(), b.scaled_data());
+    let mut diff = Diff {
+        max_error: 0.0,
+        mean_error: 0.0,
+        differing_pixels: 0,
+    };
+
+    for (l, r) in left.chunks(num_channels).zip(right.chunks(num_channels)) {
+        let mut differs = false;
+        for (&l, &r) in l.iter().zip(r.iter()) {
+            let error = if l == r || (l.is_nan() && r.is_nan()) {
+                0.0
+            } else if l.is_nan() || r.is_nan() {
+                f32::INFINITY
+            } else {
+                (l - r).abs()
+            };
+
+            diff.max_error = diff.max_error.max(error);
+            diff.mean_error += error as f64;
+            differs |= error > threshold;
+        }
+
+        if differs {
+            diff.differing_pixels += 1;
+        }
+    }
+    diff.mean_error /= left.len().max(1) as f64;
+
+    Ok(diff)
+}
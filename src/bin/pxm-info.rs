@@ -0,0 +1,51 @@
+//! Print the header, value range and invalid pixel counts of PFM files.
+//!
+//! Usage: pxm-info FILE...
+use pxm::PFM;
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::process;
+
+fn main() {
+    let paths: Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("Usage: pxm-info FILE...");
+        process::exit(2);
+    }
+
+    let mut failed = false;
+    for path in &paths {
+        if let Err(e) = print_info(path) {
+            eprintln!("{}: {}", path, e);
+            failed = true;
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn print_info(path: &str) -> Result<(), &'static str> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Err("Unable to open file"),
+    };
+    let file_size = match file.metadata() {
+        Ok(metadata) => metadata.len(),
+        Err(_) => return Err("Unable to read file metadata"),
+    };
+
+    let pfm = PFM::read_from(&mut BufReader::new(file))?;
+    let stats = pfm.stats(true);
+    let report = pfm.validate();
+
+    println!("{}: {}", path, pfm);
+    println!("  file size:  {} bytes", file_size);
+    println!("  range:      [{}, {}]", stats.global.min, stats.global.max);
+    println!("  NaN pixels: {}", report.nan_pixels);
+    println!("  Inf pixels: {}", report.inf_pixels);
+
+    Ok(())
+}
@@ -0,0 +1,263 @@
+//! Print the statistics and optionally the histograms of the finite values of
+//! PFM files, over all channels and per channel.
+//!
+//! Usage: pxm-stats [--format text|csv|json] [--bins N] FILE...
+//!
+//! Histograms have N equal bins spanning the value range of each row. Pass a
+//! shell glob such as `data/*.pfm` to process many files.
+use pxm::{Stats, PFM, PXM};
+use std::env;
+use std::process;
+
+const USAGE: &str = "Usage: pxm-stats [--format text|csv|json] [--bins N] FILE...";
+
+#[derive(Copy, Clone, PartialEq)]
+enum Output {
+    Text,
+    Csv,
+    Json,
+}
+
+/// Statistics of one channel, or of all channels if `channel` is `None`.
+struct Row {
+    channel: Option<usize>,
+    stats: Stats,
+    histogram: Vec<usize>,
+}
+
+fn main() {
+    let mut output = Output::Text;
+    let mut bins = 0;
+    let mut paths = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                output = match args.next().as_deref() {
+                    Some("text") => Output::Text,
+                    Some("csv") => Output::Csv,
+                    Some("json") => Output::Json,
+                    _ => exit_with_usage("--format must be text, csv or json"),
+                }
+            }
+            "--bins" => {
+                bins = match args.next().map(|arg| arg.parse()) {
+                    Some(Ok(bins)) => bins,
+                    _ => exit_with_usage("--bins expects a number"),
+                }
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.is_empty() {
+        exit_with_usage("Expected at least one path");
+    }
+
+    if output == Output::Csv {
+        let mut header = String::from("file,channel,count,min,max,mean,stddev");
+        for i in 0..bins {
+            header.push_str(&format!(",bin{}", i));
+        }
+        println!("{}", header);
+    } else if output == Output::Json {
+        println!("[");
+    }
+
+    let mut failed = false;
+    let mut first = true;
+    for path in &paths {
+        let rows = match load(path).map(|pfm| rows(&pfm, bins)) {
+            Ok(rows) => rows,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                failed = true;
+                continue;
+            }
+        };
+
+        match output {
+            Output::Text => print_text(path, &rows),
+            Output::Csv => print_csv(path, &rows),
+            Output::Json => {
+                if !first {
+                    println!(",");
+                }
+                print_json(path, &rows);
+            }
+        }
+        first = false;
+    }
+
+    if output == Output::Json {
+        println!("\n]");
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn load(path: &str) -> Result<PFM, &'static str> {
+    let PXM::PFM(pfm) = PXM::load(path)?;
+    Ok(pfm)
+}
+
+fn rows(pfm: &PFM, bins: usize) -> Vec<Row> {
+    let stats = pfm.stats(true);
+    let num_channels = stats.channels.len();
+
+    let mut rows = vec![Row {
+        channel: None,
+        stats: stats.global,
+        histogram: histogram(pfm.data.iter().copied(), &stats.global, bins),
+    }];
+    if num_channels > 1 {
+        for (c, &stats) in stats.channels.iter().enumerate() {
+            let values = pfm.data.iter().skip(c).step_by(num_channels).copied();
+            rows.push(Row {
+                channel: Some(c),
+                stats,
+                histogram: histogram(values, &stats, bins),
+            });
+        }
+    }
+
+    rows
+}
+
+/// Count the finite values in `bins` equal bins between `stats.min` and
+/// `stats.max`.
+fn histogram(values: impl Iterator<Item = f32>, stats: &Stats, bins: usize) -> Vec<usize> {
+    let mut histogram = vec![0; bins];
+    if bins == 0 || stats.count == 0 {
+        return histogram;
+    }
+
+    let range = (stats.max - stats.min) as f64;
+    for v in values.filter(|v| v.is_finite()) {
+        let bin = if range > 0.0 {
+            ((v - stats.min) as f64 / range * bins as f64) as usize
+        } else {
+            0
+        };
+        histogram[bin.min(bins - 1)] += 1;
+    }
+
+    histogram
+}
+
+fn channel_name(channel: Option<usize>) -> String {
+    match channel {
+        Some(c) => c.to_string(),
+        None => String::from("all"),
+    }
+}
+
+fn print_text(path: &str, rows: &[Row]) {
+    println!("{}:", path);
+    for row in rows {
+        let s = &row.stats;
+        println!(
+            "  {:>3}: count={} min={} max={} mean={} stddev={}",
+            channel_name(row.channel),
+            s.count,
+            s.min,
+            s.max,
+            s.mean,
+            s.stddev
+        );
+
+        let peak = row.histogram.iter().copied().max().unwrap_or(0).max(1);
+        let bin_width = (s.max - s.min) / row.histogram.len() as f32;
+        for (i, &n) in row.histogram.iter().enumerate() {
+            println!(
+                "       [{:>12.6}, {:>12.6}) {:>10} {}",
+                s.min + i as f32 * bin_width,
+                s.min + (i + 1) as f32 * bin_width,
+                n,
+                "#".repeat(n * 40 / peak)
+            );
+        }
+    }
+}
+
+fn print_csv(path: &str, rows: &[Row]) {
+    let path = if path.contains([',', '"', '\n']) {
+        format!("\"{}\"", path.replace('"', "\"\""))
+    } else {
+        path.to_string()
+    };
+
+    for row in rows {
+        let s = &row.stats;
+        let mut line = format!(
+            "{},{},{},{},{},{},{}",
+            path,
+            channel_name(row.channel),
+            s.count,
+            s.min,
+            s.max,
+            s.mean,
+            s.stddev
+        );
+        for n in &row.histogram {
+            line.push_str(&format!(",{}", n));
+        }
+        println!("{}", line);
+    }
+}
+
+fn print_json(path: &str, rows: &[Row]) {
+    let rows: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let s = &row.stats;
+            let histogram: Vec<String> = row.histogram.iter().map(|n| n.to_string()).collect();
+            format!(
+                "{{\"channel\": {}, \"count\": {}, \"min\": {}, \"max\": {}, \"mean\": {}, \"stddev\": {}, \"histogram\": [{}]}}",
+                row.channel.map_or(String::from("null"), |c| c.to_string()),
+                s.count,
+                json_number(s.min),
+                json_number(s.max),
+                json_number(s.mean),
+                json_number(s.stddev),
+                histogram.join(", ")
+            )
+        })
+        .collect();
+
+    print!(
+        "  {{\"file\": {}, \"stats\": [\n    {}\n  ]}}",
+        json_string(path),
+        rows.join(",\n    ")
+    );
+}
+
+fn json_number(v: f32) -> String {
+    if v.is_finite() {
+        v.to_string()
+    } else {
+        String::from("null")
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
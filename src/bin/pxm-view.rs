@@ -0,0 +1,103 @@
+//! Preview a PFM file in a truecolor terminal. The image is tone mapped,
+//! downsampled to fit the terminal width and drawn with half blocks, so that
+//! each character cell shows two pixels.
+//!
+//! Usage: pxm-view [--width COLUMNS] [--gamma G | --reinhard KEY] FILE
+//!
+//! The width defaults to `$COLUMNS`, or to 80 if it's unset.
+use pxm::{Filter, ToneMap, PFM, PXM};
+use std::env;
+use std::io::prelude::*;
+use std::io::{self, BufWriter};
+use std::process;
+
+const USAGE: &str = "Usage: pxm-view [--width COLUMNS] [--gamma G | --reinhard KEY] FILE";
+
+fn main() {
+    let mut width = env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(80);
+    let mut op = ToneMap::Gamma(2.2);
+    let mut paths = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--width" => width = parse_value(args.next()),
+            "--gamma" => op = ToneMap::Gamma(parse_value(args.next())),
+            "--reinhard" => {
+                op = ToneMap::Reinhard {
+                    key: parse_value(args.next()),
+                }
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.len() != 1 {
+        exit_with_usage("Expected a single path");
+    }
+
+    if let Err(e) = view(&paths[0], width, op) {
+        eprintln!("{}: {}", paths[0], e);
+        process::exit(1);
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(arg: Option<String>) -> T {
+    match arg.map(|arg| arg.parse()) {
+        Some(Ok(v)) => v,
+        _ => exit_with_usage("Expected a number"),
+    }
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn view(path: &str, columns: usize, op: ToneMap) -> Result<(), &'static str> {
+    let PXM::PFM(pfm) = PXM::load(path)?;
+    let pfm = if pfm.color { pfm } else { pfm.to_color()? };
+
+    // Never upsample. Rows are rounded up to an even number, as every cell
+    // covers two of them.
+    let width = columns.clamp(1, pfm.width.max(1));
+    let height = (pfm.height * width).div_ceil(pfm.width.max(1)).max(1);
+    let height = height + height % 2;
+    let preview = pfm.tonemap(op)?.resize(width, height, Filter::Bilinear)?;
+
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    match draw(&mut writer, &preview) {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Unable to write to stdout"),
+    }
+}
+
+/// Draw pairs of rows with the upper half block, using the foreground color
+/// for the upper pixel and the background color for the lower one.
+fn draw(writer: &mut impl Write, pfm: &PFM) -> io::Result<()> {
+    let row_len = pfm.width * 3;
+    for rows in pfm.data.chunks(row_len * 2) {
+        let (upper, lower) = rows.split_at(row_len);
+        for (u, l) in upper.chunks(3).zip(lower.chunks(3)) {
+            let [ur, ug, ub] = to_rgb8(u);
+            let [lr, lg, lb] = to_rgb8(l);
+            write!(
+                writer,
+                "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                ur, ug, ub, lr, lg, lb
+            )?;
+        }
+        writeln!(writer, "\x1b[0m")?;
+    }
+
+    writer.flush()
+}
+
+fn to_rgb8(pixel: &[f32]) -> [u8; 3] {
+    let quantize = |v: f32| (v * 255.0).round() as u8;
+    [quantize(pixel[0]), quantize(pixel[1]), quantize(pixel[2])]
+}
@@ -0,0 +1,185 @@
+use crate::common::RowOrder;
+use crate::options::PfmWriteOptions;
+use crate::pfm::{data_len, encode_rows, PfmHeader, PFM};
+use crate::unit::{comments_with_unit, Unit};
+use std::borrow::Cow;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+/// A PFM image whose pixel data is either borrowed or owned, so that an
+/// existing slice can be encoded without cloning it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CowPfm<'a> {
+    header: PfmHeader,
+    data: Cow<'a, [f32]>,
+    unit: Option<Unit>,
+    comments: Vec<String>,
+    row_order: RowOrder,
+}
+
+impl<'a> CowPfm<'a> {
+    /// Wrap `data`, which is in the same order as `PFM::data`.
+    pub fn new(
+        header: PfmHeader,
+        data: impl Into<Cow<'a, [f32]>>,
+    ) -> Result<CowPfm<'a>, &'static str> {
+        if header.width == 0 || header.height == 0 {
+            return Err("Invalid width or height");
+        }
+
+        let data = data.into();
+        if data_len(header.width, header.height, header.num_channels()) != Some(data.len()) {
+            return Err("The length of data is not equal to width * height * channels");
+        }
+
+        Ok(CowPfm {
+            header,
+            data,
+            unit: None,
+            comments: Vec::new(),
+            row_order: RowOrder::TopToBottom,
+        })
+    }
+
+    /// Borrow the pixel data of `pfm`. The unit, comments and row order are
+    /// kept as well.
+    pub fn from_pfm(pfm: &'a PFM) -> CowPfm<'a> {
+        CowPfm {
+            header: pfm.header(),
+            data: Cow::Borrowed(&pfm.data),
+            unit: pfm.unit,
+            comments: pfm.comments.clone(),
+            row_order: pfm.row_order,
+        }
+    }
+
+    /// Get the header of the image.
+    pub fn header(&self) -> &PfmHeader {
+        &self.header
+    }
+
+    /// Get the pixel data.
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    /// Get the pixel data mutably, cloning it first if it's borrowed.
+    pub fn data_mut(&mut self) -> &mut [f32] {
+        self.data.to_mut()
+    }
+
+    /// True if the pixel data is borrowed.
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.data, Cow::Borrowed(_))
+    }
+
+    /// Convert into a `PFM`, cloning the pixel data only if it's borrowed.
+    pub fn into_pfm(self) -> Result<PFM, &'static str> {
+        let mut pfm = PFM::from_header(&self.header, self.data.into_owned())?;
+        pfm.unit = self.unit;
+        pfm.comments = self.comments;
+        pfm.row_order = self.row_order;
+
+        Ok(pfm)
+    }
+
+    /// Encode and write the image to objects implementing `Write` trait.
+    pub fn write_into(&self, writer: &mut impl Write) -> Result<(), &'static str> {
+        self.write_into_with(writer, &mut PfmWriteOptions::new())
+    }
+
+    /// Encode and write the image to objects implementing `Write` trait with
+    /// the given options.
+    pub fn write_into_with(
+        &self,
+        writer: &mut impl Write,
+        options: &mut PfmWriteOptions,
+    ) -> Result<(), &'static str> {
+        let row_len = self.header.row_len();
+        if row_len == 0 || self.header.height == 0 {
+            return Err("Invalid width or height");
+        }
+
+        let rows = self.data.chunks(row_len);
+        let rows: Vec<&[f32]> = match self.row_order {
+            RowOrder::TopToBottom => rows.rev().collect(),
+            RowOrder::BottomToTop => rows.collect(),
+        };
+        let mut writer = BufWriter::new(writer);
+        let comments = comments_with_unit(&self.comments, self.unit);
+        encode_rows(&self.header, &comments, &rows, &mut writer, options)?;
+
+        match writer.flush() {
+            Err(_) => Err("Unable to flush data"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'a> From<PFM> for CowPfm<'a> {
+    fn from(pfm: PFM) -> CowPfm<'a> {
+        CowPfm {
+            header: pfm.header(),
+            data: Cow::Owned(pfm.data),
+            unit: pfm.unit,
+            comments: pfm.comments,
+            row_order: pfm.row_order,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Endian;
+    use crate::pfm::PFMBuilder;
+
+    #[test]
+    fn test_cow_pfm() {
+        let header = PfmHeader {
+            width: 2,
+            height: 1,
+            color: false,
+            scale_factor: 1.0,
+            endian: Endian::Little,
+        };
+        let data = [1.0, 2.0];
+        assert!(CowPfm::new(header, &data[..1]).is_err());
+
+        let mut borrowed = CowPfm::new(header, &data[..]).unwrap();
+        assert!(borrowed.is_borrowed());
+
+        let pfm = borrowed.clone().into_pfm().unwrap();
+        let mut buffer = Vec::new();
+        borrowed.write_into(&mut buffer).unwrap();
+        assert_eq!(buffer, pfm.to_bytes().unwrap());
+        assert_eq!(CowPfm::from_pfm(&pfm), borrowed);
+
+        borrowed.data_mut()[0] = 3.0;
+        assert!(!borrowed.is_borrowed());
+        assert_eq!(data, [1.0, 2.0]);
+
+        let owned = CowPfm::from(pfm);
+        assert!(!owned.is_borrowed());
+
+        let empty = PFMBuilder::new().build().unwrap();
+        assert!(CowPfm::from(empty).into_pfm().is_err());
+    }
+
+    #[test]
+    fn test_cow_pfm_bottom_up() {
+        let mut pfm = PFMBuilder::new()
+            .color(false)
+            .size(1, 2)
+            .data(vec![1.0, 2.0])
+            .comments(vec!["origin=gl".to_string()])
+            .build()
+            .unwrap();
+        pfm.set_row_order(RowOrder::BottomToTop);
+
+        let mut buffer = Vec::new();
+        CowPfm::from_pfm(&pfm).write_into(&mut buffer).unwrap();
+        assert_eq!(buffer, pfm.to_bytes().unwrap());
+        assert_eq!(CowPfm::from(pfm.clone()).into_pfm().unwrap(), pfm);
+    }
+}
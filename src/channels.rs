@@ -0,0 +1,212 @@
+use crate::pfm::PFM;
+
+/// Mutable access to the channels of a `PFM` as separate planes.
+///
+/// Created by `PFM::channels_mut`. The interleaved pixel data is split into
+/// one contiguous plane per channel, so that every plane can be borrowed
+/// independently, e.g. by different threads. The planes are written back
+/// into the image when the guard is dropped.
+#[derive(Debug)]
+pub struct ChannelsMut<'a> {
+    pfm: &'a mut PFM,
+    planes: Vec<Vec<f32>>,
+}
+
+impl<'a> ChannelsMut<'a> {
+    /// Get one mutable slice per channel, each of length width * height.
+    pub fn planes_mut(&mut self) -> Vec<&mut [f32]> {
+        self.planes.iter_mut().map(|p| p.as_mut_slice()).collect()
+    }
+
+    /// Get one slice per channel, each of length width * height.
+    pub fn planes(&self) -> Vec<&[f32]> {
+        self.planes.iter().map(|p| p.as_slice()).collect()
+    }
+}
+
+impl<'a> Drop for ChannelsMut<'a> {
+    fn drop(&mut self) {
+        let num_channels = self.planes.len();
+        for (i, pixel) in self.pfm.data.chunks_mut(num_channels).enumerate() {
+            for (v, plane) in pixel.iter_mut().zip(self.planes.iter()) {
+                *v = plane[i];
+            }
+        }
+    }
+}
+
+impl PFM {
+    /// Split the image into separately borrowable channel planes, which are
+    /// written back when the returned guard is dropped.
+    pub fn channels_mut(&mut self) -> ChannelsMut<'_> {
+        let num_channels = self.num_channels();
+        let num_pixels = self.data.len() / num_channels;
+        let mut planes = vec![Vec::with_capacity(num_pixels); num_channels];
+        for pixel in self.data.chunks(num_channels) {
+            for (plane, v) in planes.iter_mut().zip(pixel.iter()) {
+                plane.push(*v);
+            }
+        }
+
+        ChannelsMut { pfm: self, planes }
+    }
+
+    /// Split a color image into one monochrome image per channel.
+    pub fn split_channels(&self) -> Result<[PFM; 3], &'static str> {
+        if !self.color {
+            return Err("Only color images can be split into channels");
+        }
+
+        Ok([self.channel(0)?, self.channel(1)?, self.channel(2)?])
+    }
+
+    /// Extract channel `idx` as a monochrome image, keeping the scaling
+    /// factor, endianness and unit.
+    pub fn channel(&self, idx: usize) -> Result<PFM, &'static str> {
+        let num_channels = self.num_channels();
+        if idx >= num_channels {
+            return Err("The channel does not exist");
+        }
+
+        let data = self
+            .data
+            .iter()
+            .skip(idx)
+            .step_by(num_channels)
+            .copied()
+            .collect();
+
+        Ok(self.with_data(self.width, self.height, false, data))
+    }
+
+    /// Merge three monochrome images of the same size and scaling factor into
+    /// a color image. The endianness and unit are taken from `r`.
+    pub fn from_channels(r: &PFM, g: &PFM, b: &PFM) -> Result<PFM, &'static str> {
+        if r.color || g.color || b.color {
+            return Err("Channels must be monochrome images");
+        }
+
+        if (r.width, r.height) != (g.width, g.height) || (r.width, r.height) != (b.width, b.height)
+        {
+            return Err("Channels must have the same size");
+        }
+
+        if r.data.len() != g.data.len() || r.data.len() != b.data.len() {
+            return Err("Channels must have the same length of data");
+        }
+
+        if r.scale_factor != g.scale_factor || r.scale_factor != b.scale_factor {
+            return Err("Channels must have the same scaling factor");
+        }
+
+        let mut data = Vec::with_capacity(r.data.len() * 3);
+        for ((&r, &g), &b) in r.data.iter().zip(g.data.iter()).zip(b.data.iter()) {
+            data.extend_from_slice(&[r, g, b]);
+        }
+
+        Ok(r.with_data(r.width, r.height, true, data))
+    }
+
+    /// Convert a color image to a monochrome one as the weighted sum of its
+    /// channels, e.g. with `[0.2126, 0.7152, 0.0722]` for Rec. 709 luminance.
+    pub fn to_grayscale(&self, weights: [f32; 3]) -> Result<PFM, &'static str> {
+        if !self.color {
+            return Err("Only color images can be converted to grayscale");
+        }
+
+        let data = self
+            .data
+            .chunks_exact(3)
+            .map(|p| p[0] * weights[0] + p[1] * weights[1] + p[2] * weights[2])
+            .collect();
+
+        Ok(self.with_data(self.width, self.height, false, data))
+    }
+
+    /// Convert a monochrome image to a color one by replicating its channel.
+    pub fn to_color(&self) -> Result<PFM, &'static str> {
+        if self.color {
+            return Err("Only monochrome images can be converted to color");
+        }
+
+        let data = self.data.iter().flat_map(|&v| [v, v, v]).collect();
+
+        Ok(self.with_data(self.width, self.height, true, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pfm::{PFMBuilder, PFM};
+
+    #[test]
+    fn test_channels_mut() {
+        let mut pfm = PFMBuilder::new()
+            .color(true)
+            .size(2, 1)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .build()
+            .unwrap();
+
+        {
+            let mut channels = pfm.channels_mut();
+            assert_eq!(
+                channels.planes(),
+                vec![&[1.0, 4.0][..], &[2.0, 5.0], &[3.0, 6.0]]
+            );
+
+            let mut planes = channels.planes_mut();
+            planes[0][1] = 0.0;
+            planes[2][0] = -1.0;
+        }
+
+        assert_eq!(pfm.data, vec![1.0, 2.0, -1.0, 0.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn test_split_and_merge_channels() {
+        let pfm = PFMBuilder::new()
+            .color(true)
+            .size(2, 1)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .build()
+            .unwrap();
+
+        let [r, g, b] = pfm.split_channels().unwrap();
+        assert!(!r.color);
+        assert_eq!(r.data, vec![1.0, 4.0]);
+        assert_eq!(g.data, vec![2.0, 5.0]);
+        assert_eq!(b.data, vec![3.0, 6.0]);
+        assert!(r.split_channels().is_err());
+        assert_eq!(pfm.channel(1).unwrap(), g);
+        assert_eq!(r.channel(0).unwrap(), r);
+        assert!(pfm.channel(3).is_err());
+
+        assert_eq!(PFM::from_channels(&r, &g, &b).unwrap(), pfm);
+        assert!(PFM::from_channels(&r, &g, &pfm).is_err());
+
+        let mut scaled = b.clone();
+        scaled.scale_factor = 2.0;
+        assert!(PFM::from_channels(&r, &g, &scaled).is_err());
+    }
+
+    #[test]
+    fn test_grayscale_and_color() {
+        let pfm = PFMBuilder::new()
+            .color(true)
+            .size(2, 1)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .build()
+            .unwrap();
+
+        let gray = pfm.to_grayscale([0.5, 0.25, 0.25]).unwrap();
+        assert!(!gray.color);
+        assert_eq!(gray.data, vec![1.75, 4.75]);
+        assert!(gray.to_grayscale([1.0, 0.0, 0.0]).is_err());
+
+        let color = gray.to_color().unwrap();
+        assert!(color.color);
+        assert_eq!(color.data, vec![1.75, 1.75, 1.75, 4.75, 4.75, 4.75]);
+        assert!(color.to_color().is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::pfm::PFM;
+
+/// Colormap used by `PFM::apply_colormap`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Colormap {
+    Viridis,
+    Turbo,
+    Jet,
+}
+
+impl Colormap {
+    /// Get the RGB color of `t` in [0, 1]. Values outside are clamped, and NaN
+    /// is mapped to black.
+    pub fn color(self, t: f32) -> [f32; 3] {
+        if t.is_nan() {
+            return [0.0; 3];
+        }
+
+        let t = t.clamp(0.0, 1.0);
+        let rgb = match self {
+            Colormap::Viridis => polynomial_rgb(t, &VIRIDIS),
+            Colormap::Turbo => polynomial_rgb(t, &TURBO),
+            Colormap::Jet => [
+                1.5 - (4.0 * t - 3.0).abs(),
+                1.5 - (4.0 * t - 2.0).abs(),
+                1.5 - (4.0 * t - 1.0).abs(),
+            ],
+        };
+
+        [
+            rgb[0].clamp(0.0, 1.0),
+            rgb[1].clamp(0.0, 1.0),
+            rgb[2].clamp(0.0, 1.0),
+        ]
+    }
+}
+
+impl PFM {
+    /// Map a monochrome image with values in [0, 1] to a color image with
+    /// `colormap`, e.g. after `normalize`. The result has a scaling factor of
+    /// 1 and no unit.
+    pub fn apply_colormap(&self, colormap: Colormap) -> Result<PFM, &'static str> {
+        if self.color {
+            return Err("Only monochrome images can be colormapped");
+        }
+
+        let data = self.data.iter().flat_map(|&v| colormap.color(v)).collect();
+
+        let mut pfm = self.with_data(self.width, self.height, true, data);
+        pfm.scale_factor = 1.0;
+        pfm.unit = None;
+
+        Ok(pfm)
+    }
+}
+
+/// Polynomial fit of the matplotlib viridis colormap, with the coefficients
+/// of each channel in increasing order.
+const VIRIDIS: [[f32; 7]; 3] = [
+    [0.2777, 0.1051, -0.3309, -4.6342, 6.2283, 4.7764, -5.4355],
+    [0.0054, 1.4046, 0.2148, -5.7991, 14.1799, -13.7451, 4.6459],
+    [0.3341, 1.3846, 0.0951, -19.3324, 56.6906, -65.3530, 26.3124],
+];
+
+/// Polynomial fit of the Google turbo colormap, with the coefficients of each
+/// channel in increasing order.
+const TURBO: [[f32; 6]; 3] = [
+    [0.1357, 4.6154, -42.6603, 132.1311, -152.9424, 59.2864],
+    [0.0914, 2.1942, 4.8430, -14.1850, 4.2773, 2.8296],
+    [0.1067, 12.6419, -60.5820, 110.3628, -89.9031, 27.3482],
+];
+
+/// Evaluate one polynomial per channel at `t`.
+fn polynomial_rgb<const N: usize>(t: f32, coefficients: &[[f32; N]; 3]) -> [f32; 3] {
+    let eval = |c: &[f32; N]| c.iter().rev().fold(0.0, |acc, c| acc * t + c);
+    [
+        eval(&coefficients[0]),
+        eval(&coefficients[1]),
+        eval(&coefficients[2]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+
+    #[test]
+    fn test_apply_colormap() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .size(3, 1)
+            .data(vec![0.0, 0.5, f32::NAN])
+            .build()
+            .unwrap();
+
+        let jet = pfm.apply_colormap(Colormap::Jet).unwrap();
+        assert!(jet.color);
+        assert_eq!(jet.data, vec![0.0, 0.0, 0.5, 0.5, 1.0, 0.5, 0.0, 0.0, 0.0]);
+        assert!(jet.apply_colormap(Colormap::Jet).is_err());
+
+        // Dark purple to yellow.
+        let low = Colormap::Viridis.color(0.0);
+        let high = Colormap::Viridis.color(1.0);
+        assert!(low[2] > low[0] && low[2] > low[1]);
+        assert!(high[0] > 0.9 && high[1] > 0.9 && high[2] < 0.2);
+
+        let turbo = pfm.apply_colormap(Colormap::Turbo).unwrap();
+        assert!(turbo.data.iter().all(|v| (0.0..=1.0).contains(v)));
+    }
+}
@@ -1,6 +1,78 @@
+use std::slice;
+
 /// Flag indicating whether to store data in Big-endian or Little-endian format.
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Endian {
     Big,
     Little,
+    /// The endianness of the host, resolved when the file is written. Decoded
+    /// images always have `Big` or `Little`.
+    Native,
+}
+
+/// Order of the rows of `PFM::data`. PFM files store rows bottom-to-top.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub enum RowOrder {
+    #[default]
+    TopToBottom,
+    /// The order of the file, as expected by e.g. OpenGL textures.
+    BottomToTop,
+}
+
+/// Supported image file formats.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Format {
+    PFM,
+}
+
+impl Endian {
+    /// Endianness of the host platform.
+    pub fn host() -> Endian {
+        if cfg!(target_endian = "big") {
+            Endian::Big
+        } else {
+            Endian::Little
+        }
+    }
+
+    /// Resolve `Native` into the endianness of the host.
+    pub fn resolve(self) -> Endian {
+        match self {
+            Endian::Native => Endian::host(),
+            endian => endian,
+        }
+    }
+}
+
+/// Reverse the byte order of every value in place. The loop over the bits
+/// is simple enough to be vectorized by the compiler.
+pub(crate) fn swap_bytes(values: &mut [f32]) {
+    for v in values.iter_mut() {
+        *v = f32::from_bits(v.to_bits().swap_bytes());
+    }
+}
+
+/// View floats as their native-endian bytes.
+pub(crate) fn f32_as_bytes(values: &[f32]) -> &[u8] {
+    // SAFETY: `u8` has no alignment requirement and the byte length covers
+    // exactly the memory of `values`.
+    unsafe { slice::from_raw_parts(values.as_ptr() as *const u8, values.len() * 4) }
+}
+
+/// View floats as their native-endian bytes, mutably.
+pub(crate) fn f32_as_bytes_mut(values: &mut [f32]) -> &mut [u8] {
+    // SAFETY: as in `f32_as_bytes`, and every bit pattern is a valid `f32`.
+    unsafe { slice::from_raw_parts_mut(values.as_mut_ptr() as *mut u8, values.len() * 4) }
+}
+
+/// Allocate `len` copies of `value`, failing instead of aborting when a
+/// crafted header asks for more memory than can be reserved.
+pub(crate) fn try_vec<T: Clone>(len: usize, value: T) -> Result<Vec<T>, &'static str> {
+    let mut values = Vec::new();
+    if values.try_reserve_exact(len).is_err() {
+        return Err("Image too large");
+    }
+    values.resize(len, value);
+
+    Ok(values)
 }
@@ -0,0 +1,93 @@
+use crate::pfm::PFM;
+
+/// First difference found by `PFM::approx_eq`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Mismatch {
+    /// The images differ in size or color mode.
+    Shape,
+    /// The scaled values of `channel` of pixel (`x`, `y`) differ.
+    Pixel {
+        x: usize,
+        y: usize,
+        channel: usize,
+        left: f32,
+        right: f32,
+    },
+}
+
+impl PFM {
+    /// Compare the scaled values of two images, so that images with different
+    /// scaling factors can be equal. Two values `a` and `b` are equal if
+    /// `|a - b| <= abs_tol + rel_tol * max(|a|, |b|)`, or if both are NaN.
+    /// Returns the first mismatch in top to bottom, left to right order.
+    pub fn approx_eq(&self, other: &PFM, abs_tol: f32, rel_tol: f32) -> Result<(), Mismatch> {
+        if (self.width, self.height, self.color) != (other.width, other.height, other.color)
+            || self.data.len() != other.data.len()
+        {
+            return Err(Mismatch::Shape);
+        }
+
+        let num_channels = self.num_channels();
+        for (i, (&a, &b)) in self.data.iter().zip(other.data.iter()).enumerate() {
+            let (left, right) = (a * self.scale_factor, b * other.scale_factor);
+            if !is_close(left, right, abs_tol, rel_tol) {
+                let pixel = i / num_channels;
+                return Err(Mismatch::Pixel {
+                    x: pixel % self.width,
+                    y: pixel / self.width,
+                    channel: i % num_channels,
+                    left,
+                    right,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_close(a: f32, b: f32, abs_tol: f32, rel_tol: f32) -> bool {
+    if a == b || (a.is_nan() && b.is_nan()) {
+        return true;
+    }
+
+    (a - b).abs() <= abs_tol + rel_tol * a.abs().max(b.abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+
+    #[test]
+    fn test_approx_eq() {
+        let pfm = PFMBuilder::new()
+            .color(true)
+            .size(2, 1)
+            .data(vec![1.0, 2.0, f32::NAN, 4.0, 100.0, 6.0])
+            .build()
+            .unwrap();
+
+        let mut other = pfm.clone();
+        other.factor_out_scale(2.0).unwrap();
+        assert_eq!(pfm.approx_eq(&other, 0.0, 0.0), Ok(()));
+
+        other.data[4] += 0.5;
+        assert_eq!(pfm.approx_eq(&other, 0.0, 0.02), Ok(()));
+        assert_eq!(
+            pfm.approx_eq(&other, 0.5, 0.0),
+            Err(Mismatch::Pixel {
+                x: 1,
+                y: 0,
+                channel: 1,
+                left: 100.0,
+                right: 101.0
+            })
+        );
+
+        assert_eq!(
+            pfm.approx_eq(&pfm.to_grayscale([1.0; 3]).unwrap(), 0.0, 0.0),
+            Err(Mismatch::Shape)
+        );
+    }
+}
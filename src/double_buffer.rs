@@ -0,0 +1,145 @@
+use crate::pfm::PFM;
+
+/// A pair of same-sized `PFM` images used as ping-pong buffers.
+///
+/// Iterative filters usually read from one image and write into another,
+/// then exchange the roles of both images for the next iteration.
+/// `DoubleBuffer` keeps the two images together and takes care of the
+/// bookkeeping.
+#[derive(Debug, Clone)]
+pub struct DoubleBuffer {
+    buffers: [PFM; 2],
+    front: usize,
+}
+
+impl DoubleBuffer {
+    /// Create a double buffer from two images. Both images must have the same
+    /// width, height and color mode.
+    pub fn new(front: PFM, back: PFM) -> Result<DoubleBuffer, &'static str> {
+        if front.width != back.width || front.height != back.height {
+            return Err("Front and back buffers must have the same size");
+        }
+
+        if front.color != back.color {
+            return Err("Front and back buffers must have the same color mode");
+        }
+
+        Ok(DoubleBuffer {
+            buffers: [front, back],
+            front: 0,
+        })
+    }
+
+    /// Create a double buffer whose back buffer is a copy of `front`.
+    pub fn from_front(front: PFM) -> DoubleBuffer {
+        let back = front.clone();
+
+        DoubleBuffer {
+            buffers: [front, back],
+            front: 0,
+        }
+    }
+
+    /// Get the front buffer.
+    pub fn front(&self) -> &PFM {
+        &self.buffers[self.front]
+    }
+
+    /// Get the front buffer mutably.
+    pub fn front_mut(&mut self) -> &mut PFM {
+        &mut self.buffers[self.front]
+    }
+
+    /// Get the back buffer.
+    pub fn back(&self) -> &PFM {
+        &self.buffers[1 - self.front]
+    }
+
+    /// Get the back buffer mutably.
+    pub fn back_mut(&mut self) -> &mut PFM {
+        &mut self.buffers[1 - self.front]
+    }
+
+    /// Exchange the front and back buffers.
+    pub fn swap(&mut self) {
+        self.front = 1 - self.front;
+    }
+
+    /// Run `f` with the front buffer as source and the back buffer as
+    /// destination, then swap so that the result becomes the front buffer.
+    ///
+    /// `f` must not change the size or color mode of the destination.
+    pub fn apply<F>(&mut self, f: F)
+    where
+        F: FnOnce(&PFM, &mut PFM),
+    {
+        let (first, second) = self.buffers.split_at_mut(1);
+        let (src, dst) = if self.front == 0 {
+            (&first[0], &mut second[0])
+        } else {
+            (&second[0], &mut first[0])
+        };
+
+        f(src, dst);
+        debug_assert!(src.width == dst.width && src.height == dst.height && src.color == dst.color);
+
+        self.swap();
+    }
+
+    /// Consume the double buffer and return the front buffer.
+    pub fn into_front(self) -> PFM {
+        let [first, second] = self.buffers;
+        if self.front == 0 {
+            first
+        } else {
+            second
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+
+    #[test]
+    fn test_apply_swaps_buffers() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .size(2, 1)
+            .data(vec![1.0, 2.0])
+            .build()
+            .unwrap();
+        let mut buffers = DoubleBuffer::from_front(pfm);
+
+        for _ in 0..3 {
+            buffers.apply(|src, dst| {
+                for (d, s) in dst.data.iter_mut().zip(src.data.iter()) {
+                    *d = s * 2.0;
+                }
+            });
+        }
+
+        assert_eq!(buffers.front().data, vec![8.0, 16.0]);
+        assert_eq!(buffers.back().data, vec![4.0, 8.0]);
+        assert_eq!(buffers.into_front().data, vec![8.0, 16.0]);
+    }
+
+    #[test]
+    fn test_new_rejects_mismatched_sizes() {
+        let a = PFMBuilder::new()
+            .color(false)
+            .size(2, 1)
+            .data(vec![1.0, 2.0])
+            .build()
+            .unwrap();
+        let b = PFMBuilder::new()
+            .color(false)
+            .size(1, 2)
+            .data(vec![1.0, 2.0])
+            .build()
+            .unwrap();
+
+        assert!(DoubleBuffer::new(a, b).is_err());
+    }
+}
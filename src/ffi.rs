@@ -0,0 +1,162 @@
+//! C API. Images are handed out as opaque `PFM` pointers, which must be
+//! released with `pxm_free`.
+//!
+//! Build the shared library with
+//! `cargo rustc --release --features ffi --crate-type cdylib`.
+use crate::pfm::PFM;
+use std::ffi::CStr;
+use std::fs::File;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+/// Load the PFM file at the NUL-terminated `path`, whatever its extension.
+/// Returns null on failure.
+///
+/// # Safety
+///
+/// `path` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn pxm_load(path: *const c_char) -> *mut PFM {
+    if path.is_null() {
+        return ptr::null_mut();
+    }
+
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(path) => path,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    match PFM::read_from(&mut file) {
+        Ok(pfm) => Box::into_raw(Box::new(pfm)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Save `pfm` as a PFM file at the NUL-terminated `path`. Returns 0 on
+/// success and -1 on failure.
+///
+/// # Safety
+///
+/// `pfm` must be null or returned by `pxm_load`, and `path` must be null or
+/// point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn pxm_save(pfm: *const PFM, path: *const c_char) -> c_int {
+    if pfm.is_null() || path.is_null() {
+        return -1;
+    }
+
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(path) => path,
+        Err(_) => return -1,
+    };
+
+    let mut file = match File::create(path) {
+        Ok(file) => file,
+        Err(_) => return -1,
+    };
+
+    match (*pfm).write_into(&mut file) {
+        Ok(_) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Release an image returned by `pxm_load`.
+///
+/// # Safety
+///
+/// `pfm` must be null or returned by `pxm_load`, and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pxm_free(pfm: *mut PFM) {
+    if !pfm.is_null() {
+        drop(Box::from_raw(pfm));
+    }
+}
+
+/// Width of the image, or 0 if `pfm` is null.
+///
+/// # Safety
+///
+/// `pfm` must be null or returned by `pxm_load`.
+#[no_mangle]
+pub unsafe extern "C" fn pxm_width(pfm: *const PFM) -> usize {
+    pfm.as_ref().map_or(0, |pfm| pfm.width)
+}
+
+/// Height of the image, or 0 if `pfm` is null.
+///
+/// # Safety
+///
+/// `pfm` must be null or returned by `pxm_load`.
+#[no_mangle]
+pub unsafe extern "C" fn pxm_height(pfm: *const PFM) -> usize {
+    pfm.as_ref().map_or(0, |pfm| pfm.height)
+}
+
+/// Number of channels of the image (1 or 3), or 0 if `pfm` is null.
+///
+/// # Safety
+///
+/// `pfm` must be null or returned by `pxm_load`.
+#[no_mangle]
+pub unsafe extern "C" fn pxm_channels(pfm: *const PFM) -> usize {
+    pfm.as_ref().map_or(0, |pfm| pfm.num_channels())
+}
+
+/// Pointer to the width * height * channels pixel values in top to bottom,
+/// left to right order, or null if `pfm` is null. The pointer is valid until
+/// the image is freed.
+///
+/// # Safety
+///
+/// `pfm` must be null or returned by `pxm_load`.
+#[no_mangle]
+pub unsafe extern "C" fn pxm_data(pfm: *mut PFM) -> *mut f32 {
+    match pfm.as_mut() {
+        Some(pfm) => pfm.data.as_mut_ptr(),
+        None => ptr::null_mut(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+    use std::env;
+    use std::ffi::CString;
+
+    #[test]
+    fn test_ffi_round_trip() {
+        let mut path = env::temp_dir();
+        path.push("pxm_ffi_test.bin");
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+
+        let pfm = PFMBuilder::new()
+            .color(true)
+            .size(1, 2)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .build()
+            .unwrap();
+
+        unsafe {
+            assert_eq!(pxm_save(&pfm, path.as_ptr()), 0);
+
+            let loaded = pxm_load(path.as_ptr());
+            assert!(!loaded.is_null());
+            assert_eq!(pxm_width(loaded), 1);
+            assert_eq!(pxm_height(loaded), 2);
+            assert_eq!(pxm_channels(loaded), 3);
+            *pxm_data(loaded).add(5) = 0.0;
+            assert_eq!((*loaded).data, vec![1.0, 2.0, 3.0, 4.0, 5.0, 0.0]);
+            pxm_free(loaded);
+
+            assert!(pxm_load(ptr::null()).is_null());
+            assert_eq!(pxm_width(ptr::null()), 0);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use crate::pfm::PFM;
+
+impl PFM {
+    /// Compute the forward differences (`gx`, `gy`), which are zero in the last
+    /// column and row. Every channel is processed independently.
+    pub fn gradient(&self) -> (PFM, PFM) {
+        let (width, height, num_channels) = (self.width, self.height, self.num_channels());
+        let mut gx = vec![0.0f32; self.data.len()];
+        let mut gy = vec![0.0f32; self.data.len()];
+
+        for y in 0..height {
+            for x in 0..width {
+                for c in 0..num_channels {
+                    let i = (y * width + x) * num_channels + c;
+                    if x + 1 < width {
+                        gx[i] = self.data[i + num_channels] - self.data[i];
+                    }
+                    if y + 1 < height {
+                        gy[i] = self.data[i + width * num_channels] - self.data[i];
+                    }
+                }
+            }
+        }
+
+        (
+            self.with_data(width, height, self.color, gx),
+            self.with_data(width, height, self.color, gy),
+        )
+    }
+
+    /// Compute the 3x3 Sobel derivatives (`gx`, `gy`), replicating the border
+    /// pixels. Every channel is processed independently.
+    pub fn sobel(&self) -> (PFM, PFM) {
+        let (width, height, num_channels) = (self.width, self.height, self.num_channels());
+        let mut gx = vec![0.0f32; self.data.len()];
+        let mut gy = vec![0.0f32; self.data.len()];
+        let at = |x: usize, y: usize, dx: isize, dy: isize, c: usize| {
+            let x = (x as isize + dx).clamp(0, width as isize - 1) as usize;
+            let y = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+            self.data[(y * width + x) * num_channels + c]
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                for c in 0..num_channels {
+                    let i = (y * width + x) * num_channels + c;
+                    gx[i] = (at(x, y, 1, -1, c) + 2.0 * at(x, y, 1, 0, c) + at(x, y, 1, 1, c))
+                        - (at(x, y, -1, -1, c) + 2.0 * at(x, y, -1, 0, c) + at(x, y, -1, 1, c));
+                    gy[i] = (at(x, y, -1, 1, c) + 2.0 * at(x, y, 0, 1, c) + at(x, y, 1, 1, c))
+                        - (at(x, y, -1, -1, c) + 2.0 * at(x, y, 0, -1, c) + at(x, y, 1, -1, c));
+                }
+            }
+        }
+
+        (
+            self.with_data(width, height, self.color, gx),
+            self.with_data(width, height, self.color, gy),
+        )
+    }
+
+    /// Compute the divergence of the gradient field (`gx`, `gy`), where both
+    /// components are assumed to be forward differences. Backward differences
+    /// are used so that `divergence(gradient(u))` equals `laplacian(u)`.
+    /// Every channel is processed independently.
+    pub fn divergence(gx: &PFM, gy: &PFM) -> Result<PFM, &'static str> {
+        if gx.width != gy.width || gx.height != gy.height || gx.color != gy.color {
+            return Err("Gradient components must have the same size and color mode");
+        }
+
+        let (width, height, num_channels) = (gx.width, gx.height, gx.num_channels());
+        let mut data = vec![0.0f32; gx.data.len()];
+
+        for y in 0..height {
+            for x in 0..width {
+                for c in 0..num_channels {
+                    let i = (y * width + x) * num_channels + c;
+                    let mut div = 0.0;
+                    if x + 1 < width {
+                        div += gx.data[i];
+                    }
+                    if x > 0 {
+                        div -= gx.data[i - num_channels];
+                    }
+                    if y + 1 < height {
+                        div += gy.data[i];
+                    }
+                    if y > 0 {
+                        div -= gy.data[i - width * num_channels];
+                    }
+                    data[i] = div;
+                }
+            }
+        }
+
+        Ok(gx.with_data(width, height, gx.color, data))
+    }
+
+    /// Compute the 4-neighbour Laplacian with Neumann (zero-flux) boundaries.
+    /// Every channel is processed independently.
+    pub fn laplacian(&self) -> PFM {
+        let (width, height, num_channels) = (self.width, self.height, self.num_channels());
+        let mut data = vec![0.0f32; self.data.len()];
+
+        for y in 0..height {
+            for x in 0..width {
+                for c in 0..num_channels {
+                    let i = (y * width + x) * num_channels + c;
+                    data[i] = neighbours(width, height, num_channels, x, y)
+                        .map(|n| self.data[n + c] - self.data[i])
+                        .sum();
+                }
+            }
+        }
+
+        self.with_data(width, height, self.color, data)
+    }
+
+    /// Solve the Poisson equation `laplacian(u) = rhs` with Neumann boundaries
+    /// by running `iterations` Gauss-Seidel sweeps starting from `initial`.
+    ///
+    /// Because the solution is only defined up to a constant, the result
+    /// keeps the offset of `initial`.
+    pub fn poisson_solve(rhs: &PFM, initial: &PFM, iterations: usize) -> Result<PFM, &'static str> {
+        if rhs.width != initial.width || rhs.height != initial.height || rhs.color != initial.color
+        {
+            return Err("Right-hand side and initial guess must have the same size and color mode");
+        }
+
+        let (width, height, num_channels) = (rhs.width, rhs.height, rhs.num_channels());
+        let mut u = initial.clone();
+
+        for _ in 0..iterations {
+            for y in 0..height {
+                for x in 0..width {
+                    for c in 0..num_channels {
+                        let i = (y * width + x) * num_channels + c;
+                        let mut sum = 0.0;
+                        let mut count = 0.0;
+                        for n in neighbours(width, height, num_channels, x, y) {
+                            sum += u.data[n + c];
+                            count += 1.0;
+                        }
+                        if count > 0.0 {
+                            u.data[i] = (sum - rhs.data[i]) / count;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(u)
+    }
+}
+
+/// Offsets of the first channel of the 4-connected neighbours of (x, y).
+fn neighbours(
+    width: usize,
+    height: usize,
+    num_channels: usize,
+    x: usize,
+    y: usize,
+) -> impl Iterator<Item = usize> {
+    let row = width * num_channels;
+    let i = (y * width + x) * num_channels;
+    let left = if x > 0 { Some(i - num_channels) } else { None };
+    let right = if x + 1 < width {
+        Some(i + num_channels)
+    } else {
+        None
+    };
+    let up = if y > 0 { Some(i - row) } else { None };
+    let down = if y + 1 < height { Some(i + row) } else { None };
+
+    left.into_iter().chain(right).chain(up).chain(down)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::mono;
+
+    #[test]
+    fn test_divergence_of_gradient_is_laplacian() {
+        let u = mono(3, 2, vec![1.0, 2.0, 4.0, 0.0, 3.0, 9.0]);
+        let gx = mono(3, 2, vec![1.0, 2.0, 0.0, 3.0, 6.0, 0.0]);
+        let gy = mono(3, 2, vec![-1.0, 1.0, 5.0, 0.0, 0.0, 0.0]);
+
+        assert_eq!(u.gradient(), (gx.clone(), gy.clone()));
+        let div = PFM::divergence(&gx, &gy).unwrap();
+
+        assert_eq!(div, u.laplacian());
+        assert_eq!(div.data, vec![0.0, 2.0, 3.0, 4.0, 2.0, -11.0]);
+    }
+
+    #[test]
+    fn test_poisson_solve() {
+        let u = mono(3, 3, vec![1.0, 2.0, 3.0, 2.0, 5.0, 4.0, 3.0, 4.0, 6.0]);
+        let rhs = u.laplacian();
+
+        let mut initial = u.clone();
+        initial.data[4] = 0.0;
+        let solved = PFM::poisson_solve(&rhs, &initial, 200).unwrap();
+
+        let offset = solved.data[0] - u.data[0];
+        for (a, b) in solved.data.iter().zip(u.data.iter()) {
+            assert!((a - b - offset).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_sobel() {
+        // A horizontal ramp has no vertical derivative.
+        let u = mono(3, 2, vec![0.0, 1.0, 2.0, 0.0, 1.0, 2.0]);
+        let (gx, gy) = u.sobel();
+
+        assert_eq!(gx.data, vec![4.0, 8.0, 4.0, 4.0, 8.0, 4.0]);
+        assert_eq!(gy.data, vec![0.0; 6]);
+    }
+}
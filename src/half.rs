@@ -0,0 +1,240 @@
+use crate::common::{try_vec, RowOrder};
+use crate::options::PfmWriteOptions;
+use crate::pfm::{encode_rows_with, PfmHeader, PFM};
+use crate::stream::PfmReader;
+use crate::unit::{comments_with_unit, take_unit, Unit};
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+/// A PFM image whose pixel values are stored in memory as IEEE 754 half
+/// precision floats, taking half the memory of `PFM`. Values are converted
+/// from and to `f32` row by row while reading and writing the file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HalfPfm {
+    pub header: PfmHeader,
+    /// Bits of the half precision pixel values, which are stored in the top
+    /// to bottom, left to right order.
+    pub data: Vec<u16>,
+    /// Physical unit of the pixel values, if known.
+    pub unit: Option<Unit>,
+    /// Lines of the `#` comments of the header.
+    pub comments: Vec<String>,
+}
+
+impl HalfPfm {
+    /// Convert the values of `pfm` to half precision, rounding to nearest.
+    /// Rows are reordered from top to bottom if needed.
+    pub fn from_pfm(pfm: &PFM) -> HalfPfm {
+        let rows = pfm.data.chunks(pfm.width * pfm.num_channels());
+        let rows: Vec<&[f32]> = match pfm.row_order {
+            RowOrder::TopToBottom => rows.collect(),
+            RowOrder::BottomToTop => rows.rev().collect(),
+        };
+
+        HalfPfm {
+            header: pfm.header(),
+            data: rows
+                .iter()
+                .flat_map(|row| row.iter())
+                .map(|&v| f32_to_f16(v))
+                .collect(),
+            unit: pfm.unit,
+            comments: pfm.comments.clone(),
+        }
+    }
+
+    /// Convert the values back to single precision.
+    pub fn to_pfm(&self) -> Result<PFM, &'static str> {
+        let mut pfm = PFM::from_header(
+            &self.header,
+            self.data.iter().map(|&h| f16_to_f32(h)).collect(),
+        )?;
+        pfm.unit = self.unit;
+        pfm.comments = self.comments.clone();
+
+        Ok(pfm)
+    }
+
+    /// Read a PFM file, converting each row to half precision.
+    pub fn read_from(reader: &mut impl Read) -> Result<HalfPfm, &'static str> {
+        let mut reader = PfmReader::new(reader)?;
+        let header = *reader.header();
+        let mut comments = reader.comments().to_vec();
+        let unit = take_unit(&mut comments);
+        let row_len = header.row_len();
+
+        // Rows are appended in the bottom-to-top order of the file as they
+        // arrive and flipped at the end.
+        let mut data = Vec::new();
+        let mut row = try_vec(row_len, 0.0)?;
+        while reader.read_row(&mut row)?.is_some() {
+            if data.try_reserve(row_len).is_err() {
+                return Err("Image too large");
+            }
+            data.extend(row.iter().map(|&v| f32_to_f16(v)));
+        }
+
+        let mut rows = data.chunks_exact_mut(row_len);
+        while let (Some(top), Some(bottom)) = (rows.next(), rows.next_back()) {
+            top.swap_with_slice(bottom);
+        }
+
+        Ok(HalfPfm {
+            header,
+            data,
+            unit,
+            comments,
+        })
+    }
+
+    /// Write the image as a PFM file, converting each row to single precision.
+    pub fn write_into(&self, writer: &mut impl Write) -> Result<(), &'static str> {
+        let row_len = self.header.row_len();
+        if row_len == 0 || self.header.height == 0 {
+            return Err("Invalid width or height");
+        }
+
+        if self.data.len() != row_len * self.header.height {
+            return Err("The length of image data is not equal to width * height * channels specified in the header");
+        }
+
+        let mut writer = BufWriter::new(writer);
+        let comments = comments_with_unit(&self.comments, self.unit);
+        let options = &mut PfmWriteOptions::new();
+        encode_rows_with(&self.header, &comments, &mut writer, options, |i, row| {
+            let y = self.header.height - 1 - i;
+            for (dst, &h) in row.iter_mut().zip(&self.data[y * row_len..]) {
+                *dst = f16_to_f32(h);
+            }
+        })?;
+
+        match writer.flush() {
+            Ok(_) => Ok(()),
+            Err(_) => Err("Unable to write into the writer"),
+        }
+    }
+}
+
+/// Convert `v` to the bits of the nearest half precision float, rounding ties
+/// to even.
+fn f32_to_f16(v: f32) -> u16 {
+    let bits = v.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let man = bits & 0x7f_ffff;
+
+    if exp == 0xff {
+        let nan = if man != 0 {
+            0x200 | (man >> 13) as u16
+        } else {
+            0
+        };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exp = exp - 127 + 15;
+    if exp >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    // Shift out the extra mantissa bits, including the implicit leading one of
+    // values which become subnormal.
+    let (half, man, shift) = if exp <= 0 {
+        if exp < -10 {
+            return sign;
+        }
+        (0, man | 0x80_0000, (14 - exp) as u32)
+    } else {
+        ((exp as u32) << 10, man, 13)
+    };
+    let half = half | (man >> shift);
+    let rem = man & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let round_up = rem > halfway || (rem == halfway && half & 1 == 1);
+
+    // A carry out of the mantissa correctly increments the exponent.
+    sign | (half + round_up as u32) as u16
+}
+
+/// Convert the bits of a half precision float to `f32` exactly.
+fn f16_to_f32(h: u16) -> f32 {
+    let sign = ((h & 0x8000) as u32) << 16;
+    let exp = ((h >> 10) & 0x1f) as u32;
+    let man = (h & 0x3ff) as u32;
+
+    let bits = match (exp, man) {
+        (0, 0) => sign,
+        (0, _) => {
+            // Normalize the subnormal value.
+            let shift = man.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | (((man << shift) & 0x3ff) << 13)
+        }
+        (0x1f, _) => sign | 0x7f80_0000 | (man << 13),
+        _ => sign | ((exp + 127 - 15) << 23) | (man << 13),
+    };
+
+    f32::from_bits(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+
+    #[test]
+    fn test_f16_conversion() {
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(70000.0), 0x7c00);
+        assert_eq!(f32_to_f16(2.0f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_f16(2.0f32.powi(-26)), 0x0000);
+        assert_eq!(f32_to_f16(1.0 + 2.0f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 * 2.0f32.powi(-11)), 0x3c02);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+
+        for &h in &[0x0000, 0x8001, 0x03ff, 0x0400, 0x3555, 0x7bff, 0xfc00] {
+            assert_eq!(f32_to_f16(f16_to_f32(h)), h);
+        }
+        assert_eq!(f16_to_f32(0x0001), 2.0f32.powi(-24));
+    }
+
+    #[test]
+    fn test_half_pfm() {
+        let mut pfm = PFMBuilder::new()
+            .color(false)
+            .size(2, 2)
+            .data(vec![0.5, -1.0, 1024.0, 0.25])
+            .comments(vec!["made by test".to_string()])
+            .build()
+            .unwrap();
+        pfm.unit = Some(Unit::Meters);
+        let mut buffer = Vec::new();
+        pfm.write_into(&mut buffer).unwrap();
+
+        let half = HalfPfm::read_from(&mut buffer.as_slice()).unwrap();
+        assert_eq!(half, HalfPfm::from_pfm(&pfm));
+        assert_eq!(half.to_pfm().unwrap(), pfm);
+
+        let mut written = Vec::new();
+        half.write_into(&mut written).unwrap();
+        assert_eq!(written, buffer);
+
+        let truncated = b"Pf 100000 100000 -1 \0\0\x80\x3f";
+        assert!(HalfPfm::read_from(&mut &truncated[..]).is_err());
+    }
+
+    #[test]
+    fn test_half_pfm_bottom_up() {
+        let mut pfm = PFM::from_fn(2, 3, true, |x, y, c| (x + 10 * y + 100 * c) as f32);
+        pfm.set_row_order(RowOrder::BottomToTop);
+
+        let half = HalfPfm::from_pfm(&pfm);
+        let mut written = Vec::new();
+        half.write_into(&mut written).unwrap();
+        assert_eq!(written, pfm.to_bytes().unwrap());
+
+        pfm.set_row_order(RowOrder::TopToBottom);
+        assert_eq!(half.to_pfm().unwrap(), pfm);
+    }
+}
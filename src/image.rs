@@ -0,0 +1,97 @@
+use crate::pfm::PFM;
+use crate::PXM;
+
+/// Common interface of the images of all supported formats, so that generic
+/// code doesn't need to match on `PXM`.
+pub trait PxmImage {
+    /// Number of columns.
+    fn width(&self) -> usize;
+
+    /// Number of rows.
+    fn height(&self) -> usize;
+
+    /// Number of channels per pixel.
+    fn channels(&self) -> usize;
+
+    /// Get channel `c` of pixel (`x`, `y`) as a float, or `None` if it's out
+    /// of bounds.
+    fn sample(&self, x: usize, y: usize, c: usize) -> Option<f32>;
+}
+
+impl PxmImage for PFM {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn channels(&self) -> usize {
+        self.num_channels()
+    }
+
+    fn sample(&self, x: usize, y: usize, c: usize) -> Option<f32> {
+        if c >= self.num_channels() {
+            return None;
+        }
+
+        self.data.get(self.pixel_offset(x, y)? + c).copied()
+    }
+}
+
+impl PxmImage for PXM {
+    fn width(&self) -> usize {
+        PXM::width(self)
+    }
+
+    fn height(&self) -> usize {
+        PXM::height(self)
+    }
+
+    fn channels(&self) -> usize {
+        PXM::channels(self)
+    }
+
+    fn sample(&self, x: usize, y: usize, c: usize) -> Option<f32> {
+        match self {
+            PXM::PFM(pfm) => pfm.sample(x, y, c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+
+    fn sum(image: &impl PxmImage) -> f32 {
+        let mut sum = 0.0;
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                for c in 0..image.channels() {
+                    sum += image.sample(x, y, c).unwrap();
+                }
+            }
+        }
+        sum
+    }
+
+    #[test]
+    fn test_pxm_image() {
+        let pfm = PFMBuilder::new()
+            .color(true)
+            .size(2, 1)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .build()
+            .unwrap();
+
+        assert_eq!(pfm.sample(1, 0, 2), Some(6.0));
+        assert_eq!(pfm.sample(0, 0, 3), None);
+        assert_eq!(pfm.sample(2, 0, 0), None);
+
+        let pxm = PXM::PFM(pfm);
+        assert_eq!(pxm.channels(), 3);
+        assert_eq!(sum(&pxm), 21.0);
+    }
+}
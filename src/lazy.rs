@@ -0,0 +1,155 @@
+use crate::common::try_vec;
+use crate::pfm::{decode_header_with_comments, PfmHeader, PFM};
+use crate::stream::{read_pixels_at, read_raw_header};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::Path;
+
+/// A PFM file whose header is parsed on open, but whose pixel rows are only
+/// decoded when requested. Decoded rows are cached.
+#[derive(Debug)]
+pub struct LazyPfm<R: Read + Seek> {
+    reader: R,
+    header: PfmHeader,
+    comments: Vec<String>,
+    payload_start: u64,
+    rows: HashMap<usize, Vec<f32>>,
+}
+
+impl LazyPfm<BufReader<File>> {
+    /// Open a disk file and parse its header.
+    pub fn open(path: impl AsRef<Path>) -> Result<LazyPfm<BufReader<File>>, &'static str> {
+        match File::open(path) {
+            Ok(file) => LazyPfm::new(BufReader::new(file)),
+            Err(_) => Err("Unable to open pfm file"),
+        }
+    }
+}
+
+impl<R: Read + Seek> LazyPfm<R> {
+    /// Parse the header of a PFM file. The reader must be positioned at the
+    /// start of the file.
+    pub fn new(mut reader: R) -> Result<LazyPfm<R>, &'static str> {
+        let raw = read_raw_header(&mut reader)?;
+        let (header, comments, _) = decode_header_with_comments(&raw)?;
+
+        let payload_start = match reader.stream_position() {
+            Ok(pos) => pos,
+            Err(_) => return Err("Unable to seek in the reader"),
+        };
+
+        Ok(LazyPfm {
+            reader,
+            header,
+            comments,
+            payload_start,
+            rows: HashMap::new(),
+        })
+    }
+
+    /// Get the header of the PFM file.
+    pub fn header(&self) -> &PfmHeader {
+        &self.header
+    }
+
+    /// Get the comments of the header of the PFM file.
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// Get row `y` in top to bottom order, decoding it if it's not cached.
+    pub fn row(&mut self, y: usize) -> Result<&[f32], &'static str> {
+        if y >= self.header.height {
+            return Err("The row is out of the image bounds");
+        }
+
+        if !self.rows.contains_key(&y) {
+            let mut row = try_vec(self.header.row_len(), 0.0)?;
+            read_pixels_at(
+                &mut self.reader,
+                &self.header,
+                self.payload_start,
+                0,
+                y,
+                &mut row,
+            )?;
+            self.rows.insert(y, row);
+        }
+
+        match self.rows.get(&y) {
+            Some(row) => Ok(row),
+            None => Err("The row is not decoded"),
+        }
+    }
+
+    /// True if row `y` is cached.
+    pub fn is_cached(&self, y: usize) -> bool {
+        self.rows.contains_key(&y)
+    }
+
+    /// Drop all cached rows.
+    pub fn clear_cache(&mut self) {
+        self.rows.clear();
+    }
+
+    /// Decode all rows which are not cached yet, and assemble the full image
+    /// with the comments and unit of the header.
+    pub fn to_pfm(&mut self) -> Result<PFM, &'static str> {
+        let mut data = Vec::new();
+        for y in 0..self.header.height {
+            data.extend_from_slice(self.row(y)?);
+        }
+
+        let mut pfm = PFM::from_header(&self.header, data)?;
+        pfm.comments = self.comments.clone();
+        pfm.take_unit_from_comments();
+
+        Ok(pfm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+    use crate::unit::Unit;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_lazy_pfm() {
+        let mut pfm = PFMBuilder::new()
+            .color(false)
+            .size(2, 3)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .comments(vec!["made by test".to_string()])
+            .build()
+            .unwrap();
+        pfm.unit = Some(Unit::Meters);
+        let mut buffer = Vec::new();
+        pfm.write_into(&mut buffer).unwrap();
+
+        let mut lazy = LazyPfm::new(Cursor::new(buffer)).unwrap();
+        assert_eq!(*lazy.header(), pfm.header());
+        assert!(!lazy.is_cached(1));
+
+        assert_eq!(lazy.row(1).unwrap(), &[3.0, 4.0]);
+        assert!(lazy.is_cached(1));
+        assert!(!lazy.is_cached(0));
+        assert!(lazy.row(3).is_err());
+
+        assert_eq!(lazy.to_pfm().unwrap(), pfm);
+
+        lazy.clear_cache();
+        assert!(!lazy.is_cached(1));
+    }
+
+    #[test]
+    fn test_lazy_pfm_huge_header() {
+        let truncated = b"Pf 100000 100000 -1 \0\0\x80\x3f";
+        let mut lazy = LazyPfm::new(Cursor::new(&truncated[..])).unwrap();
+        assert!(lazy.row(0).is_err());
+        assert!(lazy.to_pfm().is_err());
+    }
+}
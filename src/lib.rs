@@ -2,14 +2,84 @@
 //!
 //! `pxm` is a simple loader and saver for PxM (PFM, PBM, etc) formats.
 //! Currently only `PFM` format is supported.
+mod adjust;
+mod borrowed;
+mod channels;
+mod checksum;
+mod colormap;
 mod common;
+mod compare;
+mod double_buffer;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod gradient;
+#[cfg(feature = "f16")]
+mod half;
+mod image;
+mod lazy;
+mod metadata;
+#[cfg(all(feature = "mmap", unix, target_pointer_width = "64"))]
+mod mmap;
+mod options;
 mod pfm;
+#[cfg(feature = "pfm64")]
+mod pfm64;
+mod pixel;
+mod planar;
+mod pointcloud;
+mod pool;
+pub mod sequence;
+mod sidecar;
+mod smooth;
+mod stats;
+mod stream;
+mod strided;
+mod tonemap;
+mod transform;
+mod unit;
+mod validate;
 
+pub use adjust::Normalization;
+pub use borrowed::CowPfm;
+pub use channels::ChannelsMut;
+pub use colormap::Colormap;
 pub use common::Endian;
+pub use common::Format;
+pub use common::RowOrder;
+pub use compare::Mismatch;
+pub use double_buffer::DoubleBuffer;
+#[cfg(feature = "f16")]
+pub use half::HalfPfm;
+pub use image::PxmImage;
+pub use lazy::LazyPfm;
+pub use options::{NanPolicy, PfmReadOptions, PfmWriteOptions, Progress};
+pub use pfm::DecodeStats;
 pub use pfm::PFMBuilder;
+pub use pfm::PfmHeader;
 pub use pfm::PFM;
+#[cfg(feature = "pfm64")]
+pub use pfm64::Pfm64;
+pub use pixel::Pixel;
+pub use planar::PlanarPfm;
+#[cfg(feature = "ply")]
+pub use pointcloud::write_ply;
+pub use pointcloud::Intrinsics;
+pub use pool::PfmPool;
+pub use sidecar::{Metadata, MetadataValue};
+pub use stats::{ImageStats, Stats};
+use std::convert::TryFrom;
+use std::fmt;
 use std::fs::File;
+use std::io;
+use std::io::Write;
 use std::path::Path;
+pub use stream::PfmReader;
+pub use stream::PfmWriter;
+pub use strided::StridedPfm;
+pub use tonemap::ToneMap;
+pub use transform::{Filter, PadMode};
+pub use unit::Unit;
+pub use validate::ValidationReport;
 
 /// Enum containing all supported formats.
 #[derive(Debug, PartialEq)]
@@ -18,9 +88,41 @@ pub enum PXM {
 }
 
 impl PXM {
-    /// Load pxm file from disk file.
+    /// Width of the image.
+    pub fn width(&self) -> usize {
+        match self {
+            PXM::PFM(pfm) => pfm.width,
+        }
+    }
+
+    /// Height of the image.
+    pub fn height(&self) -> usize {
+        match self {
+            PXM::PFM(pfm) => pfm.height,
+        }
+    }
+
+    /// Number of channels of each pixel.
+    pub fn channels(&self) -> usize {
+        match self {
+            PXM::PFM(pfm) => pfm.num_channels(),
+        }
+    }
+
+    /// Format of the image.
+    pub fn format(&self) -> Format {
+        match self {
+            PXM::PFM(_) => Format::PFM,
+        }
+    }
+
+    /// Load pxm file from disk file, or from stdin if `path` is `-`.
     pub fn load(path: impl AsRef<Path>) -> Result<PXM, &'static str> {
         let path = path.as_ref();
+        if path == Path::new("-") {
+            return PXM::load_stdin();
+        }
+
         let ext = match path.extension() {
             Some(e) => match e.to_str() {
                 Some(e) => e.to_lowercase(),
@@ -44,9 +146,21 @@ impl PXM {
         }
     }
 
-    /// Save pxm file to disk file.
+    /// Load pxm file from stdin. Only PFM is supported.
+    pub fn load_stdin() -> Result<PXM, &'static str> {
+        let stdin = io::stdin();
+        let mut reader = stdin.lock();
+
+        PFM::read_from(&mut reader).map(PXM::PFM)
+    }
+
+    /// Save pxm file to disk file, or to stdout if `path` is `-`.
     pub fn save(&self, path: impl AsRef<Path>) -> Result<(), &'static str> {
         let path = path.as_ref();
+        if path == Path::new("-") {
+            return self.save_stdout();
+        }
+
         let ext = match path.extension() {
             Some(e) => match e.to_str() {
                 Some(e) => e.to_lowercase(),
@@ -56,21 +170,72 @@ impl PXM {
         };
 
         match ext.as_ref() {
-            "pfm" => {
-                let mut file = match File::create(path) {
-                    Ok(file) => file,
-                    Err(_) => return Err("Unable to create pfm file"),
-                };
-                match self {
-                    PXM::PFM(pfm) => match pfm.write_into(&mut file) {
-                        Ok(_) => Ok(()),
-                        Err(e) => Err(e),
-                    },
-                }
-            }
+            "pfm" => self.save_as(path, Format::PFM),
             _ => Err("Unsupported file extension"),
         }
     }
+
+    /// Save pxm file in `format` to disk file regardless of its extension,
+    /// e.g. to `depthmap.bin`, or to stdout if `path` is `-`.
+    pub fn save_as(&self, path: impl AsRef<Path>, format: Format) -> Result<(), &'static str> {
+        let path = path.as_ref();
+        if path == Path::new("-") {
+            let stdout = io::stdout();
+            return self.write_into(&mut stdout.lock(), Some(format));
+        }
+
+        let mut file = match File::create(path) {
+            Ok(file) => file,
+            Err(_) => return Err("Unable to create pfm file"),
+        };
+
+        self.write_into(&mut file, Some(format))
+    }
+
+    /// Save pxm file to stdout in the format of the image.
+    pub fn save_stdout(&self) -> Result<(), &'static str> {
+        let stdout = io::stdout();
+        let mut writer = stdout.lock();
+
+        self.write_into(&mut writer, None)
+    }
+
+    /// Encode and write pxm file to objects implementing `Write` trait.
+    /// `format` defaults to the format of the image when it's `None`.
+    pub fn write_into(
+        &self,
+        writer: &mut impl Write,
+        format: Option<Format>,
+    ) -> Result<(), &'static str> {
+        match (self, format) {
+            (PXM::PFM(pfm), None) | (PXM::PFM(pfm), Some(Format::PFM)) => pfm.write_into(writer),
+        }
+    }
+}
+
+impl fmt::Display for PXM {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PXM::PFM(pfm) => pfm.fmt(f),
+        }
+    }
+}
+
+impl From<PFM> for PXM {
+    fn from(pfm: PFM) -> PXM {
+        PXM::PFM(pfm)
+    }
+}
+
+impl TryFrom<PXM> for PFM {
+    type Error = &'static str;
+
+    /// Extract the PFM image, failing if `pxm` is of another format.
+    fn try_from(pxm: PXM) -> Result<PFM, &'static str> {
+        match pxm {
+            PXM::PFM(pfm) => Ok(pfm),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -96,4 +261,46 @@ mod tests {
 
         assert_eq!(pxm, pxm_gt);
     }
+
+    #[test]
+    fn test_pxm_conversions() {
+        let pfm = PFM::new_filled(1, 3, true, 0.5);
+        let pxm = PXM::from(pfm.clone());
+
+        assert_eq!((pxm.width(), pxm.height(), pxm.channels()), (1, 3, 3));
+        assert_eq!(pxm.format(), Format::PFM);
+        assert_eq!(PFM::try_from(pxm), Ok(pfm));
+    }
+
+    #[test]
+    fn test_pfm_save_as() {
+        let mut path = env::temp_dir();
+        path.push("pfm_save_as_test.bin");
+
+        let pxm = PXM::from(PFM::new_filled(2, 2, false, 0.25));
+        assert!(pxm.save(&path).is_err());
+        pxm.save_as(&path, Format::PFM).unwrap();
+
+        let mut file = File::open(&path).unwrap();
+        assert_eq!(PFM::read_from(&mut file).map(PXM::PFM), Ok(pxm));
+    }
+
+    #[test]
+    fn test_pfm_write_into() {
+        let pfm_gt = PFMBuilder::new()
+            .color(false)
+            .size(2, 1)
+            .data(vec![0.5, 1.0])
+            .build()
+            .unwrap();
+        let pxm = PXM::PFM(pfm_gt.clone());
+
+        let mut buffer = Vec::new();
+        pxm.write_into(&mut buffer, None).unwrap();
+        assert_eq!(PFM::from_bytes(&buffer).unwrap(), pfm_gt);
+
+        let mut buffer_pfm = Vec::new();
+        pxm.write_into(&mut buffer_pfm, Some(Format::PFM)).unwrap();
+        assert_eq!(buffer_pfm, buffer);
+    }
 }
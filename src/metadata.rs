@@ -0,0 +1,103 @@
+use crate::pfm::PFM;
+use std::fmt::Display;
+use std::str::FromStr;
+
+impl PFM {
+    /// Iterate over the `key=value` entries stored in the header comments, in
+    /// file order. Other comments are skipped.
+    pub fn metadata(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.comments.iter().filter_map(|c| parse_entry(c))
+    }
+
+    /// Get the value of the first `key=value` comment for `key`.
+    pub fn metadata_value(&self, key: &str) -> Option<&str> {
+        self.metadata().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// Parse the value for `key`, e.g. `pfm.metadata_as::<f32>("baseline")`.
+    /// Returns `Ok(None)` if there's no entry for `key`.
+    pub fn metadata_as<T: FromStr>(&self, key: &str) -> Result<Option<T>, &'static str> {
+        match self.metadata_value(key) {
+            Some(value) => match value.parse() {
+                Ok(value) => Ok(Some(value)),
+                Err(_) => Err("Unable to parse the metadata value"),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Set the value for `key`, replacing the first existing entry or adding
+    /// a comment. Keys must be non-empty and contain neither `=` nor
+    /// whitespace, and values must fit on one line.
+    pub fn set_metadata(&mut self, key: &str, value: impl Display) -> Result<(), &'static str> {
+        if key.is_empty() || key.contains(|c: char| c == '=' || c.is_whitespace()) {
+            return Err("Invalid metadata key");
+        }
+
+        let value = value.to_string();
+        if value.contains(['\n', '\r']) {
+            return Err("Metadata values must not contain line breaks");
+        }
+
+        let entry = format!("{}={}", key, value.trim());
+        match self.metadata_index(key) {
+            Some(i) => self.comments[i] = entry,
+            None => self.comments.push(entry),
+        }
+
+        Ok(())
+    }
+
+    /// Remove the first entry for `key`, returning its value.
+    pub fn remove_metadata(&mut self, key: &str) -> Option<String> {
+        let i = self.metadata_index(key)?;
+        let entry = self.comments.remove(i);
+
+        parse_entry(&entry).map(|(_, v)| v.to_string())
+    }
+
+    fn metadata_index(&self, key: &str) -> Option<usize> {
+        self.comments
+            .iter()
+            .position(|c| parse_entry(c).is_some_and(|(k, _)| k == key))
+    }
+}
+
+/// Split a `key=value` comment, whose key has no whitespace.
+pub(crate) fn parse_entry(comment: &str) -> Option<(&str, &str)> {
+    let (key, value) = comment.split_once('=')?;
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+
+    Some((key, value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata() {
+        let mut pfm = PFM::new_filled(1, 1, false, 0.0);
+        pfm.comments.push(String::from("rendered by x = y"));
+        pfm.set_metadata("baseline", 0.16).unwrap();
+        pfm.set_metadata("focal", 1050).unwrap();
+        pfm.set_metadata("baseline", 0.2).unwrap();
+        assert!(pfm.set_metadata("bad key", 1).is_err());
+        assert!(pfm.set_metadata("note", "a\nb").is_err());
+
+        let decoded = PFM::from_bytes(&pfm.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            decoded.metadata().collect::<Vec<_>>(),
+            vec![("baseline", "0.2"), ("focal", "1050")]
+        );
+        assert_eq!(decoded.metadata_as::<f32>("baseline"), Ok(Some(0.2)));
+        assert_eq!(decoded.metadata_as::<u32>("missing"), Ok(None));
+        assert!(decoded.metadata_as::<u32>("baseline").is_err());
+
+        pfm = decoded;
+        assert_eq!(pfm.remove_metadata("focal"), Some(String::from("1050")));
+        assert_eq!(pfm.comments, vec!["rendered by x = y", "baseline=0.2"]);
+    }
+}
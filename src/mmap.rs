@@ -0,0 +1,121 @@
+use crate::pfm::PFM;
+use std::fs::File;
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::slice;
+
+const PROT_READ: c_int = 1;
+const MAP_PRIVATE: c_int = 2;
+const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+extern "C" {
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: i64,
+    ) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+/// Read-only private mapping of a whole file.
+struct Mmap {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+impl Mmap {
+    fn open(file: &File) -> Result<Mmap, &'static str> {
+        let len = match file.metadata() {
+            Ok(metadata) => metadata.len() as usize,
+            Err(_) => return Err("Unable to read file metadata"),
+        };
+
+        if len == 0 {
+            return Err("Empty file");
+        }
+
+        // SAFETY: a fresh mapping is requested, so no existing memory is
+        // affected. The result is checked against MAP_FAILED.
+        let ptr = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                PROT_READ,
+                MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+
+        if ptr == MAP_FAILED {
+            return Err("Unable to memory-map the file");
+        }
+
+        Ok(Mmap { ptr, len })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: the mapping is readable and `len` bytes long for the
+        // lifetime of `self`.
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` and `len` describe a mapping created by `Mmap::open`.
+        unsafe {
+            munmap(self.ptr, self.len);
+        }
+    }
+}
+
+impl PFM {
+    /// Create `PFM` struct by memory-mapping a disk file and decoding it from
+    /// the mapping, which avoids reading the whole file into a buffer first.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated or modified, by this or any other
+    /// process, until the function returns. Changes to a private mapping are
+    /// still visible while it's read, so they break the immutability of the
+    /// decoded bytes, and truncation makes the access fault.
+    pub unsafe fn open_mmap(path: impl AsRef<Path>) -> Result<PFM, &'static str> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return Err("Unable to open pfm file"),
+        };
+
+        let map = Mmap::open(&file)?;
+
+        PFM::from_bytes(map.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pfm::{PFMBuilder, PFM};
+    use std::env;
+    use std::fs::File;
+
+    #[test]
+    fn test_open_mmap() {
+        let mut path = env::temp_dir();
+        path.push("pfm_test_mmap.pfm");
+
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .size(2, 1)
+            .data(vec![0.5, 1.0])
+            .build()
+            .unwrap();
+        pfm.write_into(&mut File::create(&path).unwrap()).unwrap();
+
+        // SAFETY: the file is only accessed by this test.
+        assert_eq!(unsafe { PFM::open_mmap(&path) }.unwrap(), pfm);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::common::Endian;
+use std::fmt;
+
+/// Progress of a load or save, reported to the progress callback of
+/// `PfmReadOptions` and `PfmWriteOptions`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Progress {
+    /// Number of bytes read or written so far.
+    pub bytes: usize,
+    /// Number of rows decoded or encoded so far.
+    pub rows: usize,
+    /// Total number of rows, once the header is known.
+    pub total_rows: Option<usize>,
+}
+
+/// How `PFM::read_from_with` treats NaN pixel values.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub enum NanPolicy {
+    /// Keep NaN values as they are.
+    #[default]
+    Keep,
+    /// Fail to load images containing NaN values.
+    Reject,
+    /// Replace NaN values with the given value.
+    Replace(f32),
+}
+
+/// Options of `PFM::read_from_with`.
+#[derive(Default)]
+pub struct PfmReadOptions<'a> {
+    pub(crate) max_size: Option<(usize, usize)>,
+    pub(crate) max_pixels: Option<usize>,
+    pub(crate) allow_trailing_data: bool,
+    pub(crate) bottom_up: bool,
+    pub(crate) nan_policy: NanPolicy,
+    pub(crate) verify_checksum: bool,
+    pub(crate) progress: Option<Box<dyn FnMut(Progress) + 'a>>,
+    pub(crate) should_cancel: Option<Box<dyn Fn() -> bool + 'a>>,
+}
+
+impl<'a> PfmReadOptions<'a> {
+    /// Creates the default options.
+    pub fn new() -> PfmReadOptions<'a> {
+        PfmReadOptions::default()
+    }
+
+    /// Reject images wider than `max_width` or higher than `max_height`
+    /// before decoding them.
+    pub fn max_size(mut self, max_width: usize, max_height: usize) -> PfmReadOptions<'a> {
+        self.max_size = Some((max_width, max_height));
+
+        self
+    }
+
+    /// Decode at most `max_pixels` pixels. Larger images are reduced by
+    /// averaging `n` x `n` blocks with the smallest `n` that fits, while
+    /// reading row by row, so the full image is never held in memory.
+    pub fn max_pixels(mut self, max_pixels: usize) -> PfmReadOptions<'a> {
+        self.max_pixels = Some(max_pixels);
+
+        self
+    }
+
+    /// Accept files with extra bytes after the pixel payload, which some
+    /// writers append, instead of treating them as broken.
+    pub fn allow_trailing_data(mut self, allow: bool) -> PfmReadOptions<'a> {
+        self.allow_trailing_data = allow;
+
+        self
+    }
+
+    /// Keep the bottom-to-top row order of the file instead of flipping the
+    /// rows, and record it in `PFM::row_order`.
+    pub fn bottom_up(mut self, bottom_up: bool) -> PfmReadOptions<'a> {
+        self.bottom_up = bottom_up;
+
+        self
+    }
+
+    /// Set how NaN pixel values are treated. Defaults to `NanPolicy::Keep`.
+    pub fn nan_policy(mut self, policy: NanPolicy) -> PfmReadOptions<'a> {
+        self.nan_policy = policy;
+
+        self
+    }
+
+    /// Verify the payload against the `crc32=` comment written by
+    /// `PfmWriteOptions::checksum`, failing on a mismatch. Files without a
+    /// checksum are accepted, and nothing is verified with `max_pixels`.
+    pub fn verify_checksum(mut self, verify: bool) -> PfmReadOptions<'a> {
+        self.verify_checksum = verify;
+
+        self
+    }
+
+    /// Set a callback invoked while reading bytes and after decoding each row.
+    pub fn progress(mut self, progress: impl FnMut(Progress) + 'a) -> PfmReadOptions<'a> {
+        self.progress = Some(Box::new(progress));
+
+        self
+    }
+
+    /// Set a callback checked while reading bytes and decoding rows.
+    /// The operation is aborted with an error once it returns true.
+    pub fn cancel(mut self, should_cancel: impl Fn() -> bool + 'a) -> PfmReadOptions<'a> {
+        self.should_cancel = Some(Box::new(should_cancel));
+
+        self
+    }
+
+    pub(crate) fn report(&mut self, progress: Progress) {
+        if let Some(f) = self.progress.as_mut() {
+            f(progress);
+        }
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<(), &'static str> {
+        match self.should_cancel.as_ref() {
+            Some(f) if f() => Err("Cancelled"),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_size(&self, width: usize, height: usize) -> Result<(), &'static str> {
+        match self.max_size {
+            Some((max_width, max_height)) if width > max_width || height > max_height => {
+                Err("The image exceeds the size limit")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn apply_nan_policy(&self, data: &mut [f32]) -> Result<(), &'static str> {
+        match self.nan_policy {
+            NanPolicy::Keep => Ok(()),
+            NanPolicy::Reject if data.iter().any(|v| v.is_nan()) => {
+                Err("The image contains NaN values")
+            }
+            NanPolicy::Reject => Ok(()),
+            NanPolicy::Replace(value) => {
+                for v in data.iter_mut().filter(|v| v.is_nan()) {
+                    *v = value;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Debug for PfmReadOptions<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PfmReadOptions")
+            .field("max_size", &self.max_size)
+            .field("max_pixels", &self.max_pixels)
+            .field("allow_trailing_data", &self.allow_trailing_data)
+            .field("bottom_up", &self.bottom_up)
+            .field("nan_policy", &self.nan_policy)
+            .field("verify_checksum", &self.verify_checksum)
+            .field("progress", &self.progress.is_some())
+            .field("should_cancel", &self.should_cancel.is_some())
+            .finish()
+    }
+}
+
+/// Options of `PFM::write_into_with`.
+#[derive(Default)]
+pub struct PfmWriteOptions<'a> {
+    pub(crate) endian: Option<Endian>,
+    pub(crate) checksum: bool,
+    pub(crate) progress: Option<Box<dyn FnMut(Progress) + 'a>>,
+    pub(crate) should_cancel: Option<Box<dyn Fn() -> bool + 'a>>,
+}
+
+impl<'a> PfmWriteOptions<'a> {
+    /// Creates the default options.
+    pub fn new() -> PfmWriteOptions<'a> {
+        PfmWriteOptions::default()
+    }
+
+    /// Write the file in `endian` instead of the endianness of the image.
+    pub fn endian(mut self, endian: Endian) -> PfmWriteOptions<'a> {
+        self.endian = Some(endian);
+
+        self
+    }
+
+    /// Embed the CRC-32 of the pixel payload as a `crc32=` header comment, to
+    /// be checked by `PfmReadOptions::verify_checksum`. Stale checksum
+    /// comments of the image are never written.
+    pub fn checksum(mut self, checksum: bool) -> PfmWriteOptions<'a> {
+        self.checksum = checksum;
+
+        self
+    }
+
+    /// Set a callback invoked after encoding each row and while writing bytes.
+    pub fn progress(mut self, progress: impl FnMut(Progress) + 'a) -> PfmWriteOptions<'a> {
+        self.progress = Some(Box::new(progress));
+
+        self
+    }
+
+    /// Set a callback checked while encoding rows and writing bytes.
+    /// The operation is aborted with an error once it returns true.
+    pub fn cancel(mut self, should_cancel: impl Fn() -> bool + 'a) -> PfmWriteOptions<'a> {
+        self.should_cancel = Some(Box::new(should_cancel));
+
+        self
+    }
+
+    pub(crate) fn report(&mut self, progress: Progress) {
+        if let Some(f) = self.progress.as_mut() {
+            f(progress);
+        }
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<(), &'static str> {
+        match self.should_cancel.as_ref() {
+            Some(f) if f() => Err("Cancelled"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'a> fmt::Debug for PfmWriteOptions<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PfmWriteOptions")
+            .field("endian", &self.endian)
+            .field("checksum", &self.checksum)
+            .field("progress", &self.progress.is_some())
+            .field("should_cancel", &self.should_cancel.is_some())
+            .finish()
+    }
+}
@@ -1,12 +1,19 @@
-use crate::common::Endian;
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+use crate::checksum::{Crc32, CHECKSUM_KEY};
+use crate::common::{f32_as_bytes, f32_as_bytes_mut, swap_bytes, try_vec, Endian, RowOrder};
+use crate::metadata::parse_entry;
+use crate::options::{PfmReadOptions, PfmWriteOptions, Progress};
+use crate::stream::{read_downsampled, read_raw_header};
+use crate::unit::{comments_with_unit, Unit};
+use std::fmt;
 use std::io::prelude::*;
-use std::io::Cursor;
+use std::io::{self, BufWriter, IoSlice};
+use std::ops::Range;
 use std::str;
+use std::time::{Duration, Instant};
 
 /// PFM struct contains all the information about a PFM file.
 /// Note that
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct PFM {
     /// Width of image.
     pub width: usize,
@@ -19,43 +26,329 @@ pub struct PFM {
     /// Endianness of pixel value in the PFM file.
     pub endian: Endian,
     /// Raw pixel values which are stored in the top to bottom, left
-    /// to right order **without** dividing the scale factor.
+    /// to right order **without** dividing the scale factor. Use
+    /// `scaled_data` to get the values multiplied by the scale factor.
     pub data: Vec<f32>,
+    /// Physical unit of the pixel values, if known. It is stored as a
+    /// `unit=` header comment.
+    pub unit: Option<Unit>,
+    /// Lines of the `#` comments of the header, without the `#` and the
+    /// surrounding whitespace. They are written back after the magic number.
+    pub comments: Vec<String>,
+    /// Order of the rows of `data`, which is honored when writing the file.
+    /// Everything else treats the first row of `data` as the top row, so
+    /// convert with `set_row_order` before other operations.
+    pub row_order: RowOrder,
 }
 
 impl PFM {
     /// Create `PFM` struct from objects implementing `Read` trait.
     pub fn read_from(reader: &mut impl Read) -> Result<PFM, &'static str> {
-        let mut buffer = Vec::new();
-        match reader.read_to_end(&mut buffer) {
-            Ok(bytes) => {
-                if bytes == 0 {
-                    return Err("Empty file");
+        PFM::read_from_with(reader, &mut PfmReadOptions::new())
+    }
+
+    /// Create `PFM` struct from objects implementing `Read` trait with the
+    /// given options.
+    pub fn read_from_with(
+        reader: &mut impl Read,
+        options: &mut PfmReadOptions,
+    ) -> Result<PFM, &'static str> {
+        let mut pfm = match options.max_pixels {
+            Some(max_pixels) => {
+                let mut pfm = read_downsampled(reader, max_pixels, options)?;
+                if options.bottom_up {
+                    pfm.set_row_order(RowOrder::BottomToTop);
                 }
+                pfm
             }
-            Err(_) => return Err("Unable to read from file"),
+            None => PFM::read_all(reader, options)?,
         };
+        options.apply_nan_policy(&mut pfm.data)?;
+
+        Ok(pfm)
+    }
+
+    fn read_all(reader: &mut impl Read, options: &mut PfmReadOptions) -> Result<PFM, &'static str> {
+        options.check_cancelled()?;
+        let mut first = [0u8];
+        loop {
+            match reader.read(&mut first) {
+                Ok(0) => return Err("Empty file"),
+                Ok(_) => break,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return Err("Unable to read from file"),
+            }
+        }
+
+        // The header is checked against the limits before the payload is read,
+        // and only as many bytes as it announces are read, plus one value to
+        // detect trailing data.
+        let mut buffer = read_raw_header(&mut (&first[..]).chain(&mut *reader))?;
+        let (header, _, _) = decode_header_with_comments(&buffer)?;
+        options.check_size(header.width, header.height)?;
+        let payload_len = header.row_len() * header.height * 4;
+        let mut payload = reader.take(payload_len as u64 + 4);
+
+        let mut chunk = vec![0u8; CHUNK_SIZE];
+        loop {
+            options.check_cancelled()?;
+            match payload.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return Err("Unable to read from file"),
+            }
+            options.report(Progress {
+                bytes: buffer.len(),
+                rows: 0,
+                total_rows: None,
+            });
+        }
+
+        decode(&buffer, None, options)
+    }
+
+    /// Create `PFM` struct by decoding an in-memory PFM file.
+    pub fn from_bytes(buffer: &[u8]) -> Result<PFM, &'static str> {
+        if buffer.is_empty() {
+            return Err("Empty file");
+        }
+
+        decode(buffer, None, &mut PfmReadOptions::new())
+    }
+
+    /// Like `from_bytes`, but also collect statistics about the decoding.
+    pub fn from_bytes_with_stats(buffer: &[u8]) -> Result<(PFM, DecodeStats), &'static str> {
+        if buffer.is_empty() {
+            return Err("Empty file");
+        }
+
+        let mut stats = DecodeStats::default();
+        let pfm = decode(buffer, Some(&mut stats), &mut PfmReadOptions::new())?;
+
+        Ok((pfm, stats))
+    }
+
+    /// Check from the header whether an in-memory PFM file is stored in the
+    /// host endianness, i.e. whether decoding it needs no byte swapping.
+    pub fn is_native_endian_file(buffer: &[u8]) -> Result<bool, &'static str> {
+        let (builder, _) = parse_header(buffer)?;
 
-        decode(&buffer)
+        Ok(builder.0.endian == Endian::host())
+    }
+
+    /// True if the image will be encoded in the host endianness. Set
+    /// `endian` to `Endian::host()` to re-encode it canonically.
+    pub fn is_native_endian(&self) -> bool {
+        self.endian.resolve() == Endian::host()
     }
 
     /// Encode and write `PFM` to objects implementing `Write` trait.
     pub fn write_into(&self, writer: &mut impl Write) -> Result<(), &'static str> {
-        let buffer = encode(&self)?;
-        match writer.write_all(&buffer) {
-            Ok(_) => match writer.flush() {
-                Err(_) => Err("Unable to flush data"),
-                _ => Ok(()),
+        self.write_into_with(writer, &mut PfmWriteOptions::new())
+    }
+
+    /// Encode and write `PFM` to objects implementing `Write` trait with the
+    /// given options.
+    pub fn write_into_with(
+        &self,
+        writer: &mut impl Write,
+        options: &mut PfmWriteOptions,
+    ) -> Result<(), &'static str> {
+        let mut writer = BufWriter::with_capacity(CHUNK_SIZE, writer);
+        encode(self, &mut writer, options)?;
+
+        match writer.flush() {
+            Err(_) => Err("Unable to flush data"),
+            _ => Ok(()),
+        }
+    }
+
+    /// Encode `PFM` into an in-memory PFM file. This is the counterpart of
+    /// `from_bytes` and needs no file system, e.g. on `wasm32`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, &'static str> {
+        let mut buffer = Vec::new();
+        self.encode_into(&mut buffer)?;
+
+        Ok(buffer)
+    }
+
+    /// Encode `PFM` into `buffer`, replacing its contents but reusing its
+    /// allocation, e.g. when saving frames at a high rate.
+    pub fn encode_into(&self, buffer: &mut Vec<u8>) -> Result<(), &'static str> {
+        buffer.clear();
+        buffer.reserve(self.data.len() * 4 + 32);
+
+        encode(self, buffer, &mut PfmWriteOptions::new())
+    }
+
+    /// Create a `width` x `height` image with every value set to `value`, a
+    /// scaling factor of 1 and the host endianness.
+    pub fn new_filled(width: usize, height: usize, color: bool, value: f32) -> PFM {
+        PFM::from_fn(width, height, color, |_, _, _| value)
+    }
+
+    /// Create a `width` x `height` image whose channel `c` of pixel (`x`, `y`)
+    /// is `f(x, y, c)`, with a scaling factor of 1 and the host endianness.
+    pub fn from_fn<F>(width: usize, height: usize, color: bool, mut f: F) -> PFM
+    where
+        F: FnMut(usize, usize, usize) -> f32,
+    {
+        let num_channels = if color { 3 } else { 1 };
+        let mut data = Vec::with_capacity(width * height * num_channels);
+        for y in 0..height {
+            for x in 0..width {
+                for c in 0..num_channels {
+                    data.push(f(x, y, c));
+                }
+            }
+        }
+
+        PFM {
+            width,
+            height,
+            color,
+            scale_factor: 1.0,
+            endian: Endian::host(),
+            data,
+            unit: None,
+            comments: Vec::new(),
+            row_order: RowOrder::TopToBottom,
+        }
+    }
+
+    /// Turn the image back into a builder, e.g. to change some fields and
+    /// rebuild it with validation.
+    pub fn into_builder(self) -> PFMBuilder {
+        PFMBuilder(self)
+    }
+
+    /// Like `into_builder`, but keeps the image by cloning it.
+    pub fn to_builder(&self) -> PFMBuilder {
+        PFMBuilder(self.clone())
+    }
+
+    /// Get the header fields of the image.
+    pub fn header(&self) -> PfmHeader {
+        PfmHeader {
+            width: self.width,
+            height: self.height,
+            color: self.color,
+            scale_factor: self.scale_factor,
+            endian: self.endian,
+        }
+    }
+
+    /// Create a new image from header fields and pixel data.
+    pub(crate) fn from_header(header: &PfmHeader, data: Vec<f32>) -> Result<PFM, &'static str> {
+        if header.width == 0 || header.height == 0 {
+            return Err("Invalid width or height");
+        }
+
+        let mut pfm = PFMBuilder::new()
+            .color(header.color)
+            .size(header.width, header.height)
+            .data(data)
+            .build()?;
+        pfm.scale_factor = header.scale_factor;
+        pfm.endian = header.endian;
+
+        Ok(pfm)
+    }
+
+    /// Number of channels of each pixel.
+    pub(crate) fn num_channels(&self) -> usize {
+        if self.color {
+            3
+        } else {
+            1
+        }
+    }
+
+    /// Create a new image sharing the scaling factor, endianness, unit and
+    /// comments of `self`.
+    pub(crate) fn with_data(
+        &self,
+        width: usize,
+        height: usize,
+        color: bool,
+        data: Vec<f32>,
+    ) -> PFM {
+        PFM {
+            width,
+            height,
+            color,
+            scale_factor: self.scale_factor,
+            endian: self.endian,
+            data,
+            unit: self.unit,
+            comments: self.comments.clone(),
+            row_order: self.row_order,
+        }
+    }
+}
+
+impl fmt::Display for PFM {
+    /// Summarize the header, e.g. `PFM 1920x1080 RGB scale=1 little-endian
+    /// (24.9 MB)`, where the size is the one of the pixel data.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PFM {}x{} {} scale={} {} ({:.1} MB)",
+            self.width,
+            self.height,
+            if self.color { "RGB" } else { "mono" },
+            self.scale_factor,
+            match self.endian.resolve() {
+                Endian::Big => "big-endian",
+                _ => "little-endian",
             },
-            Err(_) => Err("Unable to write into the writer"),
+            (self.data.len() * 4) as f64 / 1e6
+        )
+    }
+}
+
+/// Header fields of a PFM file.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct PfmHeader {
+    /// Width of image.
+    pub width: usize,
+    /// Height of image.
+    pub height: usize,
+    /// True for RGB image, False for monochrome image.
+    pub color: bool,
+    /// Scaling factor of pixel values.
+    pub scale_factor: f32,
+    /// Endianness of pixel value in the PFM file.
+    pub endian: Endian,
+}
+
+impl PfmHeader {
+    /// Number of channels of each pixel.
+    pub fn num_channels(&self) -> usize {
+        if self.color {
+            3
+        } else {
+            1
         }
     }
+
+    /// Number of values in each row.
+    pub fn row_len(&self) -> usize {
+        self.width * self.num_channels()
+    }
 }
 
 /// Provides the tool to create PFM struct, and fill in all needed information by hand.
 #[derive(Debug)]
 pub struct PFMBuilder(PFM);
 
+impl Default for PFMBuilder {
+    fn default() -> PFMBuilder {
+        PFMBuilder::new()
+    }
+}
+
 impl PFMBuilder {
     /// Creates an empty PFM struct.
     pub fn new() -> PFMBuilder {
@@ -64,8 +357,11 @@ impl PFMBuilder {
             height: 0,
             color: true,
             scale_factor: 1.0,
-            endian: Endian::Little,
+            endian: Endian::host(),
             data: Vec::new(),
+            unit: None,
+            comments: Vec::new(),
+            row_order: RowOrder::TopToBottom,
         };
 
         PFMBuilder(pfm)
@@ -110,11 +406,42 @@ impl PFMBuilder {
         self
     }
 
+    /// Set the pixel data from rows in top to bottom order. Call it after
+    /// `size` and `color`, since every row must have width * channels values.
+    pub fn data_from_rows(
+        mut self,
+        rows: impl Iterator<Item = Vec<f32>>,
+    ) -> Result<PFMBuilder, &'static str> {
+        let row_len = self.0.width * self.0.num_channels();
+        let mut data = Vec::with_capacity(row_len * self.0.height);
+        for row in rows {
+            if row.len() != row_len {
+                return Err("The length of a row is not equal to width * channels");
+            }
+            data.extend_from_slice(&row);
+        }
+        self.0.data = data;
+
+        Ok(self)
+    }
+
+    /// Set the physical unit of the pixel values.
+    pub fn unit(mut self, unit: Unit) -> PFMBuilder {
+        self.0.unit = Some(unit);
+
+        self
+    }
+
+    /// Set the comments of the header.
+    pub fn comments(mut self, comments: Vec<String>) -> PFMBuilder {
+        self.0.comments = comments;
+        self
+    }
+
     /// Build to get the final PFM struct.
     pub fn build(self) -> Result<PFM, &'static str> {
         let num_channels = if self.0.color { 3 } else { 1 };
-        let num_pixels = self.0.width * self.0.height;
-        if self.0.data.len() != num_channels * num_pixels {
+        if data_len(self.0.width, self.0.height, num_channels) != Some(self.0.data.len()) {
             return Err("The length of data is not equal to width * height * channels");
         }
 
@@ -122,91 +449,443 @@ impl PFMBuilder {
     }
 }
 
-fn encode(pfm: &PFM) -> Result<Vec<u8>, &'static str> {
+/// Size of the chunks in which files are read and written.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+fn encode(
+    pfm: &PFM,
+    writer: &mut impl Write,
+    options: &mut PfmWriteOptions,
+) -> Result<(), &'static str> {
     if pfm.width == 0 || pfm.height == 0 {
         return Err("Invalid width or height");
     }
 
-    if pfm.scale_factor == 0.0 {
-        return Err("Invalid scaling factor");
+    if data_len(pfm.width, pfm.height, pfm.num_channels()) != Some(pfm.data.len()) {
+        return Err("The length of image data is not equal to width * height * channels specified in the header");
     }
 
-    let scale = match pfm.endian {
-        Endian::Little => -1.0 * pfm.scale_factor,
-        Endian::Big => pfm.scale_factor,
-    };
-    let header = if pfm.color { "PF" } else { "Pf" };
-    let num_channels = if pfm.color { 3 } else { 1 };
+    encode_rows(
+        &pfm.header(),
+        &comments_with_unit(&pfm.comments, pfm.unit),
+        &file_rows(pfm),
+        writer,
+        options,
+    )
+}
 
-    if pfm.width * pfm.height * num_channels != pfm.data.len() {
+/// Encode a PFM file with the given header and comments, whose `rows` are in
+/// the bottom-to-top order of the file.
+pub(crate) fn encode_rows(
+    header: &PfmHeader,
+    comments: &[String],
+    rows: &[&[f32]],
+    writer: &mut impl Write,
+    options: &mut PfmWriteOptions,
+) -> Result<(), &'static str> {
+    let row_len = header.row_len();
+    if rows.len() != header.height || rows.iter().any(|row| row.len() != row_len) {
         return Err("The length of image data is not equal to width * height * channels specified in the header");
     }
 
+    let endian = options.endian.unwrap_or(header.endian).resolve();
+    let crc = options.checksum.then(|| payload_crc(rows, endian));
+
+    // Rows in the host endianness are handed to the writer without copying.
     let mut buffer = Vec::new();
+    let encoded_header = encode_header_with_checksum(header, comments, endian, crc);
+    write_chunks(
+        header,
+        &encoded_header,
+        4,
+        writer,
+        options,
+        |range, writer| {
+            if endian == Endian::host() {
+                let mut slices: Vec<IoSlice> = rows[range]
+                    .iter()
+                    .map(|row| IoSlice::new(f32_as_bytes(row)))
+                    .collect();
+                write_all_vectored(writer, &mut slices)
+            } else {
+                buffer.clear();
+                for row in &rows[range] {
+                    encode_values(row, endian, &mut buffer);
+                }
+                writer.write_all(&buffer)
+            }
+        },
+    )
+}
 
-    buffer.extend_from_slice(header.as_bytes());
-    buffer.push(b'\n');
+/// Encode a PFM file like `encode_rows`, but produce row `i` of the file only
+/// when it's written, by filling `row` with `fill_row(i, row)`. This converts
+/// images stored in other layouts one row at a time. Every row is filled
+/// twice when a checksum is written.
+pub(crate) fn encode_rows_with(
+    header: &PfmHeader,
+    comments: &[String],
+    writer: &mut impl Write,
+    options: &mut PfmWriteOptions,
+    mut fill_row: impl FnMut(usize, &mut [f32]),
+) -> Result<(), &'static str> {
+    let endian = options.endian.unwrap_or(header.endian).resolve();
+    let mut row = try_vec(header.row_len(), 0.0)?;
+    let mut buffer = Vec::new();
 
-    buffer.extend_from_slice(format!("{} {}\n", pfm.width, pfm.height).as_bytes());
+    let mut crc = None;
+    if options.checksum {
+        let mut payload_crc = Crc32::new();
+        for i in 0..header.height {
+            fill_row(i, &mut row);
+            buffer.clear();
+            encode_values(&row, endian, &mut buffer);
+            payload_crc.update(&buffer);
+        }
+        crc = Some(payload_crc.finish());
+    }
 
-    buffer.extend_from_slice(format!("{}\n", scale).as_bytes());
+    let encoded_header = encode_header_with_checksum(header, comments, endian, crc);
+    write_chunks(
+        header,
+        &encoded_header,
+        4,
+        writer,
+        options,
+        |range, writer| {
+            buffer.clear();
+            for i in range {
+                fill_row(i, &mut row);
+                encode_values(&row, endian, &mut buffer);
+            }
+            writer.write_all(&buffer)
+        },
+    )
+}
 
-    buffer.reserve(pfm.width * pfm.height * num_channels * 4);
+/// Encode `header` in `endian` with `comments` and the `crc` of the payload if
+/// there's one, replacing any checksum comment.
+fn encode_header_with_checksum(
+    header: &PfmHeader,
+    comments: &[String],
+    endian: Endian,
+    crc: Option<u32>,
+) -> Vec<u8> {
+    let mut comments: Vec<String> = comments
+        .iter()
+        .filter(|c| !is_checksum(c))
+        .cloned()
+        .collect();
+    if let Some(crc) = crc {
+        comments.push(format!("{}={:08x}", CHECKSUM_KEY, crc));
+    }
 
-    for row in (0..pfm.height).rev() {
-        for col in 0..(pfm.width * num_channels) {
-            let cursor = row * pfm.width * num_channels + col;
-            match pfm.endian {
-                Endian::Little => buffer.write_f32::<LittleEndian>(pfm.data[cursor]).unwrap(),
-                Endian::Big => buffer.write_f32::<BigEndian>(pfm.data[cursor]).unwrap(),
-            }
+    encode_header_with_comments(&PfmHeader { endian, ..*header }, &comments)
+}
+
+/// Write `encoded_header` followed by the rows of an image with `header`,
+/// whose samples are `sample_size` bytes long. `write_chunk` writes the rows
+/// for a range of row indices in file order at a time.
+pub(crate) fn write_chunks<W: Write>(
+    header: &PfmHeader,
+    encoded_header: &[u8],
+    sample_size: usize,
+    writer: &mut W,
+    options: &mut PfmWriteOptions,
+    mut write_chunk: impl FnMut(Range<usize>, &mut W) -> io::Result<()>,
+) -> Result<(), &'static str> {
+    if header.width == 0 || header.height == 0 {
+        return Err("Invalid width or height");
+    }
+
+    if header.scale_factor == 0.0 {
+        return Err("Invalid scaling factor");
+    }
+
+    options.check_cancelled()?;
+
+    if writer.write_all(encoded_header).is_err() {
+        return Err("Unable to write into the writer");
+    }
+
+    // Rows are written in chunks of about `CHUNK_SIZE` bytes.
+    let row_bytes = header.row_len() * sample_size;
+    let rows_per_chunk = (CHUNK_SIZE / row_bytes).max(1);
+    let mut written = encoded_header.len();
+    for start in (0..header.height).step_by(rows_per_chunk) {
+        options.check_cancelled()?;
+
+        let end = (start + rows_per_chunk).min(header.height);
+        if write_chunk(start..end, writer).is_err() {
+            return Err("Unable to write into the writer");
+        }
+
+        for row in start..end {
+            written += row_bytes;
+            options.report(Progress {
+                bytes: written,
+                rows: row + 1,
+                total_rows: Some(header.height),
+            });
         }
     }
 
-    Ok(buffer)
+    Ok(())
 }
 
-fn decode(buffer: &[u8]) -> Result<PFM, &'static str> {
+/// Rows of `pfm` in the bottom-to-top order of the file.
+fn file_rows(pfm: &PFM) -> Vec<&[f32]> {
+    let rows = pfm.data.chunks(pfm.width * pfm.num_channels());
+    match pfm.row_order {
+        RowOrder::TopToBottom => rows.rev().collect(),
+        RowOrder::BottomToTop => rows.collect(),
+    }
+}
+
+fn is_checksum(comment: &str) -> bool {
+    parse_entry(comment).is_some_and(|(key, _)| key == CHECKSUM_KEY)
+}
+
+/// CRC-32 of the bytes of `rows` as they're written in `endian`.
+fn payload_crc(rows: &[&[f32]], endian: Endian) -> u32 {
+    let mut crc = Crc32::new();
+    let mut buffer = Vec::new();
+    for row in rows {
+        buffer.clear();
+        encode_values(row, endian, &mut buffer);
+        crc.update(&buffer);
+    }
+
+    crc.finish()
+}
+
+/// Check the payload against the checksum comment, if there's one.
+fn verify_checksum(comments: &[String], payload: &[u8]) -> Result<(), &'static str> {
+    let expected = comments
+        .iter()
+        .filter_map(|c| parse_entry(c))
+        .find(|(key, _)| *key == CHECKSUM_KEY);
+    let expected = match expected {
+        Some((_, value)) => match u32::from_str_radix(value, 16) {
+            Ok(crc) => crc,
+            Err(_) => return Err("Invalid checksum comment"),
+        },
+        None => return Ok(()),
+    };
+
+    let mut crc = Crc32::new();
+    crc.update(payload);
+    if crc.finish() != expected {
+        return Err("Checksum mismatch. The pixel data is corrupted");
+    }
+
+    Ok(())
+}
+
+/// Write all of `slices`, like the unstable `Write::write_all_vectored`.
+fn write_all_vectored(writer: &mut impl Write, mut slices: &mut [IoSlice]) -> io::Result<()> {
+    IoSlice::advance_slices(&mut slices, 0);
+    while !slices.is_empty() {
+        match writer.write_vectored(slices) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => IoSlice::advance_slices(&mut slices, n),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Append the bytes of `values` stored in `endian` to `buffer`. When `endian`
+/// is the host endianness the bytes are copied without conversion, otherwise
+/// they are swapped in bulk after the copy.
+pub(crate) fn encode_values(values: &[f32], endian: Endian, buffer: &mut Vec<u8>) {
+    let start = buffer.len();
+    buffer.extend_from_slice(f32_as_bytes(values));
+
+    if endian.resolve() != Endian::host() {
+        for bytes in buffer[start..].chunks_exact_mut(4) {
+            bytes.reverse();
+        }
+    }
+}
+
+/// Decode the floats stored in `endian` in `buffer` into `values`, which must
+/// have 4 bytes of `buffer` per element. When `endian` is not the host
+/// endianness the values are swapped in bulk after the copy.
+pub(crate) fn decode_values(buffer: &[u8], endian: Endian, values: &mut [f32]) {
+    f32_as_bytes_mut(values).copy_from_slice(buffer);
+
+    if endian.resolve() != Endian::host() {
+        swap_bytes(values);
+    }
+}
+
+/// Statistics collected by `PFM::from_bytes_with_stats`.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct DecodeStats {
+    /// Size of the header in bytes.
+    pub header_bytes: usize,
+    /// Size of the pixel payload in bytes.
+    pub payload_bytes: usize,
+    /// True if the file is stored in the host endianness, so the payload was
+    /// decoded without byte swapping.
+    pub native_endian: bool,
+    /// Time spent parsing the header.
+    pub header_time: Duration,
+    /// Time spent converting the payload into floats.
+    pub payload_time: Duration,
+}
+
+pub(crate) fn encode_header(header: &PfmHeader) -> Vec<u8> {
+    encode_header_with_comments(header, &[])
+}
+
+/// Encode `header` with a `#` comment line after the magic number for every
+/// line of `comments`.
+pub(crate) fn encode_header_with_comments(header: &PfmHeader, comments: &[String]) -> Vec<u8> {
+    let magic = if header.color { "PF" } else { "Pf" };
+    let mut buffer = encode_header_with_magic(header, magic);
+
+    let mut encoded = Vec::new();
+    for line in comments.iter().flat_map(|c| c.split('\n')) {
+        match line.trim() {
+            "" => encoded.extend_from_slice(b"#\n"),
+            line => encoded.extend_from_slice(format!("# {}\n", line).as_bytes()),
+        }
+    }
+    let after_magic = magic.len() + 1;
+    buffer.splice(after_magic..after_magic, encoded);
+
+    buffer
+}
+
+/// Encode `header` with the given magic number instead of 'PF' or 'Pf'.
+pub(crate) fn encode_header_with_magic(header: &PfmHeader, magic: &str) -> Vec<u8> {
+    let scale = match header.endian.resolve() {
+        Endian::Big => header.scale_factor,
+        _ => -header.scale_factor,
+    };
+
+    let mut buffer = Vec::new();
+
+    buffer.extend_from_slice(magic.as_bytes());
+    buffer.push(b'\n');
+
+    buffer.extend_from_slice(format!("{} {}\n", header.width, header.height).as_bytes());
+
+    buffer.extend_from_slice(format!("{}\n", scale).as_bytes());
+
+    buffer
+}
+
+fn decode(
+    buffer: &[u8],
+    mut stats: Option<&mut DecodeStats>,
+    options: &mut PfmReadOptions,
+) -> Result<PFM, &'static str> {
+    let start = stats.as_ref().map(|_| Instant::now());
+    let total_bytes = buffer.len();
     let (mut builder, buffer) = parse_header(buffer)?;
+    if let (Some(stats), Some(start)) = (stats.as_mut(), start) {
+        stats.header_time = start.elapsed();
+        stats.header_bytes = total_bytes - buffer.len();
+        stats.payload_bytes = buffer.len();
+        stats.native_endian = builder.0.endian == Endian::host();
+    }
 
     let endian = builder.0.endian;
     let num_channels = if builder.0.color { 3 } else { 1 };
     let height = builder.0.height;
     let width = builder.0.width;
-    let num_pixels = width * height;
+    options.check_size(width, height)?;
+    let num_values = match data_len(width, height, num_channels) {
+        Some(n) => n,
+        None => return Err("Invalid width or height"),
+    };
 
-    if num_pixels * num_channels != buffer.len() / 4 {
+    let payload_len = buffer.len() / 4;
+    if payload_len < num_values || (payload_len > num_values && !options.allow_trailing_data) {
         return Err("Broken file. The length of image data is not equal to width * height * channels specified in the header");
     }
 
-    let mut data = vec![0.0f32; num_pixels * num_channels];
-    let mut buffer = Cursor::new(buffer);
+    let start = stats.as_ref().map(|_| Instant::now());
+    let mut data = vec![0.0f32; num_values];
+    let row_len = width * num_channels;
+    let header_len = total_bytes - buffer.len();
+    let payload = &buffer[..num_values * 4];
+    if options.verify_checksum {
+        verify_checksum(&builder.0.comments, payload)?;
+    }
 
-    match endian {
-        Endian::Little => match buffer.read_f32_into::<LittleEndian>(&mut data) {
-            Err(_) => return Err("File data is broken"),
-            _ => (),
-        },
-        Endian::Big => match buffer.read_f32_into::<BigEndian>(&mut data) {
-            Err(_) => return Err("File data is broken"),
-            _ => (),
-        },
-    };
+    // Rows are stored bottom-to-top, so each one is decoded straight into its
+    // final position.
+    for (i, src) in payload.chunks(row_len * 4).enumerate() {
+        options.check_cancelled()?;
+        let y = if options.bottom_up { i } else { height - 1 - i };
+        decode_values(src, endian, &mut data[y * row_len..(y + 1) * row_len]);
+        options.report(Progress {
+            bytes: header_len + (i + 1) * row_len * 4,
+            rows: i + 1,
+            total_rows: Some(height),
+        });
+    }
 
-    for row in 0..height {
-        if row >= height - 1 - row {
-            break;
-        }
-        for col in 0..(width * num_channels) {
-            let a = row * width * num_channels + col;
-            let b = (height - 1 - row) * width * num_channels + col;
-            data.swap(a, b);
-        }
+    if let (Some(stats), Some(start)) = (stats.as_mut(), start) {
+        stats.payload_time = start.elapsed();
     }
 
     builder = builder.data(data);
+    if options.bottom_up {
+        builder.0.row_order = RowOrder::BottomToTop;
+    }
+
+    let mut pfm = builder.build()?;
+    pfm.take_unit_from_comments();
+
+    Ok(pfm)
+}
+
+/// Parse the header of a PFM file, returning the header, its comments and the
+/// pixel payload.
+pub(crate) fn decode_header_with_comments(
+    buffer: &[u8],
+) -> Result<(PfmHeader, Vec<String>, &[u8]), &'static str> {
+    let (builder, buffer) = parse_header(buffer)?;
+    let header = builder.0.header();
+    match data_len(header.width, header.height, header.num_channels()) {
+        Some(n) if n.checked_mul(4).is_some() => (),
+        _ => return Err("Invalid width or height"),
+    }
+
+    Ok((header, builder.0.comments, buffer))
+}
+
+/// Parse the header of a PFM-like file whose magic number is 'P' followed by
+/// `color` or `mono`, and whose samples are `sample_size` bytes long.
+#[cfg(feature = "pfm64")]
+pub(crate) fn decode_header_with_magic(
+    buffer: &[u8],
+    color: u8,
+    mono: u8,
+    sample_size: usize,
+) -> Result<(PfmHeader, &[u8]), &'static str> {
+    let (magic, buffer) = read_until_space(buffer)?;
+    let builder = match magic {
+        [b'P', c] if *c == color => PFMBuilder::new().color(true),
+        [b'P', c] if *c == mono => PFMBuilder::new().color(false),
+        _ => return Err("Invalid magic number"),
+    };
+
+    let (builder, buffer) = parse_size_and_scale(builder, buffer)?;
+    let header = builder.0.header();
+    match data_len(header.width, header.height, header.num_channels()) {
+        Some(n) if n.checked_mul(sample_size).is_some() => (),
+        _ => return Err("Invalid width or height"),
+    }
 
-    builder.build()
+    Ok((header, buffer))
 }
 
 fn parse_header(buffer: &[u8]) -> Result<(PFMBuilder, &[u8]), &'static str> {
@@ -216,26 +895,40 @@ fn parse_header(buffer: &[u8]) -> Result<(PFMBuilder, &[u8]), &'static str> {
 
     let (header_pf, buffer) = read_until_space(buffer)?;
 
-    if header_pf[0] != ('P' as u8) {
+    if header_pf.len() != 2 {
+        return Err("The magic number must be 'PF' or 'Pf'");
+    }
+
+    if header_pf[0] != b'P' {
         return Err("Tht first character must be 'P'");
     }
 
-    if header_pf[1] == ('F' as u8) {
+    if header_pf[1] == b'F' {
         builder = builder.color(true);
-    } else if header_pf[1] == ('f' as u8) {
+    } else if header_pf[1] == b'f' {
         builder = builder.color(false);
     } else {
         return Err("Tht second character must be 'F' or 'f'");
     }
 
+    parse_size_and_scale(builder, buffer)
+}
+
+/// Parse the width, height and scale following the magic number.
+fn parse_size_and_scale(
+    mut builder: PFMBuilder,
+    buffer: &[u8],
+) -> Result<(PFMBuilder, &[u8]), &'static str> {
     // Parse width and height
 
+    let buffer = skip_comments(buffer, &mut builder.0.comments);
     let (header_width, buffer) = read_until_space(buffer)?;
     let width: usize = parse_token(header_width, "Invalid width")?;
     if width == 0 {
         return Err("Invalid width");
     }
 
+    let buffer = skip_comments(buffer, &mut builder.0.comments);
     let (header_height, buffer) = read_until_space(buffer)?;
     let height: usize = parse_token(header_height, "Invalid height")?;
     if height == 0 {
@@ -246,17 +939,28 @@ fn parse_header(buffer: &[u8]) -> Result<(PFMBuilder, &[u8]), &'static str> {
 
     // Parse scale and endian
 
+    let buffer = skip_comments(buffer, &mut builder.0.comments);
     let (header_scale, buffer) = read_until_space(buffer)?;
-    let scale: f32 = parse_token(header_scale, "Invalid scale")?;;
-    if scale == 0.0 {
+    let scale: f32 = parse_token(header_scale, "Invalid scale")?;
+    if scale == 0.0 || !scale.is_finite() {
         return Err("Invalid scale");
     }
 
     builder = builder.scale(scale);
 
+    // A single whitespace character separates the header from the data
+    if buffer.is_empty() {
+        return Err("Reached EOF before finishing parsing");
+    }
+
     Ok((builder, &buffer[1..]))
 }
 
+/// Number of values of an image, or `None` on overflow.
+pub(crate) fn data_len(width: usize, height: usize, num_channels: usize) -> Option<usize> {
+    width.checked_mul(height)?.checked_mul(num_channels)
+}
+
 fn parse_token<T>(buffer: &[u8], err_msg: &'static str) -> Result<T, &'static str>
 where
     T: str::FromStr,
@@ -264,9 +968,33 @@ where
     match str::from_utf8(buffer) {
         Ok(s) => match s.parse() {
             Ok(w) => Ok(w),
-            Err(_) => return Err(err_msg),
+            Err(_) => Err(err_msg),
         },
-        Err(_) => return Err(err_msg),
+        Err(_) => Err(err_msg),
+    }
+}
+
+/// Skip whitespace and `#` comments, which run until the end of the line,
+/// appending the comments to `comments`. Comments may only precede the width,
+/// height and scale, as a single whitespace character separates the scale
+/// from the pixel data.
+fn skip_comments<'a>(mut buffer: &'a [u8], comments: &mut Vec<String>) -> &'a [u8] {
+    loop {
+        let start = buffer
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .unwrap_or(buffer.len());
+        buffer = &buffer[start..];
+        if buffer.first() != Some(&b'#') {
+            return buffer;
+        }
+
+        let end = buffer
+            .iter()
+            .position(|&b| b == b'\n' || b == b'\r')
+            .unwrap_or(buffer.len());
+        comments.push(String::from_utf8_lossy(&buffer[1..end]).trim().to_string());
+        buffer = &buffer[end..];
     }
 }
 
@@ -294,9 +1022,29 @@ fn read_until_space(buffer: &[u8]) -> Result<(&[u8], &[u8]), &'static str> {
     Ok((&buffer[start..end], &buffer[end..]))
 }
 
+/// Build a `width` x `height` monochrome image from `data`, for tests.
+#[cfg(test)]
+pub(crate) fn mono(width: usize, height: usize, data: Vec<f32>) -> PFM {
+    PFMBuilder::new()
+        .color(false)
+        .size(width, height)
+        .data(data)
+        .build()
+        .unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::options::NanPolicy;
+    use byteorder::{BigEndian, ByteOrder, LittleEndian};
+    use std::io::Cursor;
+
+    fn encode_to_vec(pfm: &PFM) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        encode(pfm, &mut buffer, &mut PfmWriteOptions::new()).unwrap();
+        buffer
+    }
 
     #[test]
     fn test_read_from() {
@@ -314,7 +1062,7 @@ mod tests {
 
         let pfm = PFM::read_from(&mut buffer).unwrap();
 
-        assert_eq!(pfm.color, true);
+        assert!(pfm.color);
         assert_eq!(pfm.endian, Endian::Little);
         assert_eq!(pfm.scale_factor, 1.0);
         assert_eq!(pfm.height, 3);
@@ -322,6 +1070,148 @@ mod tests {
         assert_eq!(pfm.data, vec![0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 1.0, 1.0, 1.0])
     }
 
+    #[test]
+    fn test_from_bytes() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .scale(2.0)
+            .size(2, 1)
+            .data(vec![0.25, -1.0])
+            .build()
+            .unwrap();
+        let buffer = encode_to_vec(&pfm);
+
+        assert_eq!(PFM::from_bytes(&buffer).unwrap(), pfm);
+        assert!(PFM::from_bytes(&[]).is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_with_stats() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .scale(1.0)
+            .size(2, 1)
+            .data(vec![0.25, -1.0])
+            .build()
+            .unwrap();
+        let buffer = encode_to_vec(&pfm);
+
+        let (decoded, stats) = PFM::from_bytes_with_stats(&buffer).unwrap();
+        assert_eq!(decoded, pfm);
+        assert_eq!(stats.header_bytes, 9);
+        assert_eq!(stats.payload_bytes, 8);
+        assert_eq!(stats.native_endian, Endian::host() == Endian::Big);
+        assert_eq!(stats.native_endian, pfm.is_native_endian());
+        assert_eq!(
+            PFM::is_native_endian_file(&buffer).unwrap(),
+            stats.native_endian
+        );
+    }
+
+    #[test]
+    fn test_progress() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .size(2, 3)
+            .data(vec![0.0; 6])
+            .build()
+            .unwrap();
+
+        let mut write_rows = Vec::new();
+        let mut buffer = Vec::new();
+        pfm.write_into_with(
+            &mut buffer,
+            &mut PfmWriteOptions::new().progress(|p| write_rows.push(p.rows)),
+        )
+        .unwrap();
+        assert_eq!(write_rows, vec![1, 2, 3]);
+
+        let mut last = None;
+        PFM::read_from_with(
+            &mut Cursor::new(&buffer),
+            &mut PfmReadOptions::new().progress(|p| last = Some(p)),
+        )
+        .unwrap();
+        assert_eq!(
+            last,
+            Some(Progress {
+                bytes: buffer.len(),
+                rows: 3,
+                total_rows: Some(3),
+            })
+        );
+    }
+
+    #[test]
+    fn test_cancel() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .size(2, 3)
+            .data(vec![0.0; 6])
+            .build()
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        pfm.write_into(&mut buffer).unwrap();
+
+        let checks = std::cell::Cell::new(0);
+        let result = PFM::read_from_with(
+            &mut Cursor::new(&buffer),
+            &mut PfmReadOptions::new().cancel(|| {
+                checks.set(checks.get() + 1);
+                checks.get() > 3
+            }),
+        );
+        assert_eq!(result, Err("Cancelled"));
+
+        let mut sink = Vec::new();
+        let result = pfm.write_into_with(&mut sink, &mut PfmWriteOptions::new().cancel(|| true));
+        assert_eq!(result, Err("Cancelled"));
+        assert!(sink.is_empty());
+    }
+
+    #[test]
+    fn test_encode_decode_values() {
+        let values = [1.0f32, -0.5, f32::INFINITY];
+        for endian in [Endian::Little, Endian::Big].iter() {
+            let mut buffer = Vec::new();
+            encode_values(&values, *endian, &mut buffer);
+
+            let mut expected = vec![0.0f32; 3];
+            match endian {
+                Endian::Little => LittleEndian::read_f32_into(&buffer, &mut expected),
+                Endian::Big => BigEndian::read_f32_into(&buffer, &mut expected),
+                Endian::Native => unreachable!(),
+            }
+            assert_eq!(expected, values);
+
+            let mut decoded = vec![0.0f32; 3];
+            decode_values(&buffer, *endian, &mut decoded);
+            assert_eq!(decoded, values);
+        }
+    }
+
+    #[test]
+    fn test_write_endian_override() {
+        let little = PFMBuilder::new()
+            .color(false)
+            .scale(-1.0)
+            .size(2, 1)
+            .data(vec![0.5, 2.0])
+            .build()
+            .unwrap();
+        let mut big = little.clone();
+        big.endian = Endian::Big;
+
+        let mut buffer = Vec::new();
+        little
+            .write_into_with(&mut buffer, &mut PfmWriteOptions::new().endian(Endian::Big))
+            .unwrap();
+
+        assert_eq!(buffer, encode_to_vec(&big));
+        assert_eq!(little.endian, Endian::Little);
+    }
+
     #[test]
     fn test_write_into() {
         let pfm = PFMBuilder::new()
@@ -348,6 +1238,242 @@ mod tests {
         pfm.write_into(&mut buffer).unwrap();
 
         assert_eq!(buffer, buffer_gt);
+
+        let mut buffer = pfm.to_bytes().unwrap();
+        assert_eq!(buffer, buffer_gt);
+        let capacity = buffer.capacity();
+        pfm.encode_into(&mut buffer).unwrap();
+        assert_eq!(buffer, buffer_gt);
+        assert_eq!(buffer.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_write_partial() {
+        /// Writer accepting at most 5 bytes per call.
+        struct Trickle(Vec<u8>);
+
+        impl Write for Trickle {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let n = buf.len().min(5);
+                self.0.extend_from_slice(&buf[..n]);
+                Ok(n)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let pfm = PFM::from_fn(3, 4, true, |x, y, c| (x * 100 + y * 10 + c) as f32);
+        for &endian in [Endian::Little, Endian::Big].iter() {
+            let mut options = PfmWriteOptions::new().endian(endian);
+            let mut expected = Vec::new();
+            pfm.write_into_with(&mut expected, &mut options).unwrap();
+
+            let mut writer = Trickle(Vec::new());
+            encode(&pfm, &mut writer, &mut options).unwrap();
+            assert_eq!(writer.0, expected);
+            assert_eq!(PFM::from_bytes(&writer.0).unwrap().data, pfm.data);
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        let buffer = b"Pf\n# made by  a renderer \n#\n1 # width\n1\n-1\n\0\0\x80\x3f";
+        let pfm = PFM::from_bytes(buffer).unwrap();
+        assert_eq!(pfm.comments, vec!["made by  a renderer", "", "width"]);
+        assert_eq!(pfm.data, vec![1.0]);
+
+        let encoded = pfm.to_bytes().unwrap();
+        assert!(encoded.starts_with(b"Pf\n# made by  a renderer\n#\n# width\n1 1\n-1\n"));
+        assert_eq!(PFM::from_bytes(&encoded).unwrap(), pfm);
+
+        let mut pfm = pfm;
+        pfm.comments = vec![String::from("two\nlines")];
+        let decoded = PFM::from_bytes(&pfm.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.comments, vec!["two", "lines"]);
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut pfm = PFM::from_fn(3, 2, true, |x, y, c| (x + y + c) as f32);
+        pfm.comments.push(String::from("crc32=00000000"));
+        let mut buffer = Vec::new();
+        pfm.write_into_with(
+            &mut buffer,
+            &mut PfmWriteOptions::new().endian(Endian::Big).checksum(true),
+        )
+        .unwrap();
+
+        let verify = |buffer: &[u8]| {
+            PFM::read_from_with(
+                &mut Cursor::new(buffer),
+                &mut PfmReadOptions::new().verify_checksum(true),
+            )
+        };
+        let decoded = verify(&buffer).unwrap();
+        assert_eq!(decoded.comments.len(), 1);
+        assert_eq!(decoded.data, pfm.data);
+
+        *buffer.last_mut().unwrap() ^= 1;
+        assert!(verify(&buffer).is_err());
+        assert!(PFM::from_bytes(&buffer).is_ok());
+
+        pfm.comments.clear();
+        assert!(verify(&pfm.to_bytes().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_read_options() {
+        let pfm = PFM::from_fn(2, 1, false, |x, _, _| if x == 0 { f32::NAN } else { 1.0 });
+        let mut buffer = pfm.to_bytes().unwrap();
+        let read = |buffer: &[u8], options: PfmReadOptions| {
+            PFM::read_from_with(&mut Cursor::new(buffer), &mut { options })
+        };
+
+        let options = PfmReadOptions::new().nan_policy(NanPolicy::Replace(0.0));
+        assert_eq!(read(&buffer, options).unwrap().data, vec![0.0, 1.0]);
+        let options = PfmReadOptions::new().nan_policy(NanPolicy::Reject);
+        assert!(read(&buffer, options).is_err());
+        assert!(read(&buffer, PfmReadOptions::new().max_size(1, 1)).is_err());
+
+        buffer.extend_from_slice(b"trailer");
+        assert!(read(&buffer, PfmReadOptions::new()).is_err());
+        let options = PfmReadOptions::new().allow_trailing_data(true);
+        assert_eq!(read(&buffer, options).unwrap().data[1], 1.0);
+    }
+
+    #[test]
+    fn test_read_from_checks_header_first() {
+        /// Fails every read, standing in for a payload that must not be read.
+        struct FailingReader;
+
+        impl Read for FailingReader {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::Other.into())
+            }
+        }
+
+        let huge = b"Pf 100000 100000 -1 \0\0\x80\x3f";
+        assert!(PFM::read_from(&mut Cursor::new(&huge[..])).is_err());
+
+        // The payload of an oversized image is never read.
+        let mut options = PfmReadOptions::new().max_size(10, 10);
+        let mut reader = (&huge[..20]).chain(FailingReader);
+        assert_eq!(
+            PFM::read_from_with(&mut reader, &mut options),
+            Err("The image exceeds the size limit")
+        );
+
+        let mut buffer = PFM::new_filled(1, 1, false, 1.0).to_bytes().unwrap();
+        buffer.extend_from_slice(b"trailer");
+        let mut reader = Cursor::new(&buffer);
+        let mut options = PfmReadOptions::new().allow_trailing_data(true);
+        PFM::read_from_with(&mut reader, &mut options).unwrap();
+        assert!(reader.position() < buffer.len() as u64);
+    }
+
+    #[test]
+    fn test_native_endian() {
+        let mut pfm = PFM::new_filled(1, 1, false, 2.0);
+        assert_eq!(pfm.endian, Endian::host());
+        pfm.endian = Endian::Native;
+        assert!(pfm.is_native_endian());
+
+        let mut resolved = pfm.clone();
+        resolved.endian = Endian::host();
+        assert_eq!(pfm.to_bytes(), resolved.to_bytes());
+        assert_eq!(PFM::from_bytes(&pfm.to_bytes().unwrap()), Ok(resolved));
+    }
+
+    #[test]
+    fn test_bottom_up() {
+        let pfm = PFM::from_fn(1, 3, false, |_, y, _| y as f32);
+        let buffer = pfm.to_bytes().unwrap();
+
+        let mut options = PfmReadOptions::new().bottom_up(true);
+        let mut bottom_up = PFM::read_from_with(&mut Cursor::new(&buffer), &mut options).unwrap();
+        assert_eq!(bottom_up.row_order, RowOrder::BottomToTop);
+        assert_eq!(bottom_up.data, vec![2.0, 1.0, 0.0]);
+        assert_eq!(bottom_up.to_bytes().unwrap(), buffer);
+
+        bottom_up.set_row_order(RowOrder::TopToBottom);
+        assert_eq!(bottom_up, pfm);
+    }
+
+    #[test]
+    fn test_new_filled_and_from_fn() {
+        let pfm = PFM::new_filled(2, 1, true, 0.5);
+        assert_eq!(pfm.data, vec![0.5; 6]);
+        assert_eq!(pfm.to_builder().build(), Ok(pfm));
+
+        let pfm = PFM::from_fn(2, 2, false, |x, y, _| (x + 10 * y) as f32);
+        assert_eq!(pfm.data, vec![0.0, 1.0, 10.0, 11.0]);
+
+        let pfm = PFM::from_fn(1, 1, true, |_, _, c| c as f32);
+        assert_eq!(pfm.data, vec![0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_to_builder() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .scale(-1.0)
+            .size(2, 1)
+            .data(vec![1.0, 2.0])
+            .build()
+            .unwrap();
+
+        let scaled = pfm.to_builder().scale(-4.0).build().unwrap();
+        assert_eq!(scaled.scale_factor, 4.0);
+        assert_eq!(scaled.data, pfm.data);
+
+        assert!(pfm.into_builder().data(vec![1.0]).build().is_err());
+
+        let header = PfmHeader {
+            width: 0,
+            height: 2,
+            color: false,
+            scale_factor: 1.0,
+            endian: Endian::Little,
+        };
+        assert!(PFM::from_header(&header, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_data_from_rows() {
+        let rows = vec![vec![1.0, 2.0], vec![3.0, 4.0]];
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .size(2, 2)
+            .data_from_rows(rows.into_iter())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(pfm.data, vec![1.0, 2.0, 3.0, 4.0]);
+
+        let rows = vec![vec![1.0, 2.0, 3.0], vec![4.0]];
+        assert!(PFMBuilder::new()
+            .color(false)
+            .size(2, 2)
+            .data_from_rows(rows.into_iter())
+            .is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let pfm = PFMBuilder::new()
+            .color(true)
+            .scale(-1.0)
+            .size(1000, 500)
+            .data(vec![0.0; 1_500_000])
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            pfm.to_string(),
+            "PFM 1000x500 RGB scale=1 little-endian (6.0 MB)"
+        );
     }
 
     #[test]
@@ -366,4 +1492,41 @@ mod tests {
         assert_eq!(s, "token3".as_bytes());
         assert_eq!(buffer, "".as_bytes());
     }
+
+    #[test]
+    fn test_decode_malformed_input() {
+        let crafted: Vec<&[u8]> = vec![
+            b"P",
+            b"PF",
+            b"PFF 1 1 -1 ",
+            b"PF 1 1 -1",
+            b"PF 1 1 nan ",
+            b"PF 1 1 inf ",
+            b"PF 18446744073709551615 18446744073709551615 -1 ",
+            b"Pf 4294967296 4294967296 -1 \0\0\0\0",
+        ];
+        for buffer in crafted {
+            assert!(decode(buffer, None, &mut PfmReadOptions::new()).is_err());
+        }
+
+        let pfm = PFMBuilder::new()
+            .color(true)
+            .size(2, 2)
+            .data(vec![0.5; 12])
+            .build()
+            .unwrap();
+        let valid = encode_to_vec(&pfm);
+
+        for len in 0..valid.len() {
+            assert!(decode(&valid[..len], None, &mut PfmReadOptions::new()).is_err());
+        }
+
+        for i in 0..valid.len() {
+            for byte in [0x00, b' ', b'-', b'0', b'f', 0xff].iter() {
+                let mut mutated = valid.clone();
+                mutated[i] = *byte;
+                let _ = decode(&mutated, None, &mut PfmReadOptions::new());
+            }
+        }
+    }
 }
@@ -0,0 +1,166 @@
+use crate::common::{Endian, RowOrder};
+use crate::options::PfmWriteOptions;
+use crate::pfm::{
+    decode_header_with_magic, encode_header_with_magic, write_chunks, PfmHeader, PFM,
+};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+/// A double precision image in the **non-standard** PFM extension with the
+/// magic numbers 'PD' (color) and 'Pd' (monochrome), as written by some
+/// scientific tools. The layout is the same as PFM, but every sample is an
+/// 8-byte float. Other PFM readers will reject these files.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Pfm64 {
+    pub header: PfmHeader,
+    /// Raw pixel values which are stored in the top to bottom, left to right
+    /// order.
+    pub data: Vec<f64>,
+}
+
+impl Pfm64 {
+    /// Convert `pfm` to double precision without loss. Rows are reordered
+    /// from top to bottom if needed.
+    pub fn from_pfm(pfm: &PFM) -> Pfm64 {
+        let rows = pfm.data.chunks(pfm.width * pfm.num_channels());
+        let rows: Vec<&[f32]> = match pfm.row_order {
+            RowOrder::TopToBottom => rows.collect(),
+            RowOrder::BottomToTop => rows.rev().collect(),
+        };
+
+        Pfm64 {
+            header: pfm.header(),
+            data: rows
+                .iter()
+                .flat_map(|row| row.iter())
+                .map(|&v| v as f64)
+                .collect(),
+        }
+    }
+
+    /// Convert the values to single precision, rounding to nearest.
+    pub fn to_pfm(&self) -> Result<PFM, &'static str> {
+        PFM::from_header(&self.header, self.data.iter().map(|&v| v as f32).collect())
+    }
+
+    /// Load an image from objects implementing `Read` trait.
+    pub fn read_from(reader: &mut impl Read) -> Result<Pfm64, &'static str> {
+        let mut buffer = Vec::new();
+        if reader.read_to_end(&mut buffer).is_err() {
+            return Err("Unable to read from the reader");
+        }
+
+        Pfm64::from_bytes(&buffer)
+    }
+
+    /// Decode an image from the bytes of a 'PD' or 'Pd' file.
+    pub fn from_bytes(buffer: &[u8]) -> Result<Pfm64, &'static str> {
+        let (header, payload) = decode_header_with_magic(buffer, b'D', b'd', 8)?;
+        let row_len = header.row_len();
+        if payload.len() / 8 != row_len * header.height {
+            return Err("Broken file. The length of image data is not equal to width * height * channels specified in the header");
+        }
+
+        let mut data = vec![0.0; row_len * header.height];
+        for (dst, src) in data
+            .chunks_mut(row_len)
+            .rev()
+            .zip(payload.chunks(row_len * 8))
+        {
+            if header.endian == Endian::Big {
+                BigEndian::read_f64_into(src, dst);
+            } else {
+                LittleEndian::read_f64_into(src, dst);
+            }
+        }
+
+        Ok(Pfm64 { header, data })
+    }
+
+    /// Save the image into objects implementing `Write` trait.
+    pub fn write_into(&self, writer: &mut impl Write) -> Result<(), &'static str> {
+        let header = PfmHeader {
+            endian: self.header.endian.resolve(),
+            ..self.header
+        };
+        let row_len = header.row_len();
+        if self.data.len() != row_len * header.height {
+            return Err("The length of image data is not equal to width * height * channels specified in the header");
+        }
+
+        let magic = if header.color { "PD" } else { "Pd" };
+        let encoded_header = encode_header_with_magic(&header, magic);
+        let mut writer = BufWriter::new(writer);
+        let options = &mut PfmWriteOptions::new();
+        let mut buffer = Vec::new();
+        write_chunks(
+            &header,
+            &encoded_header,
+            8,
+            &mut writer,
+            options,
+            |range, writer| {
+                buffer.clear();
+                for i in range {
+                    let y = header.height - 1 - i;
+                    for &v in &self.data[y * row_len..(y + 1) * row_len] {
+                        match header.endian {
+                            Endian::Big => buffer.extend_from_slice(&v.to_be_bytes()),
+                            _ => buffer.extend_from_slice(&v.to_le_bytes()),
+                        }
+                    }
+                }
+                writer.write_all(&buffer)
+            },
+        )?;
+
+        match writer.flush() {
+            Ok(_) => Ok(()),
+            Err(_) => Err("Unable to write into the writer"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+
+    #[test]
+    fn test_pfm64_round_trip() {
+        let pfm = PFMBuilder::new()
+            .color(true)
+            .size(1, 2)
+            .scale(-1.0)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .build()
+            .unwrap();
+        let mut image = Pfm64::from_pfm(&pfm);
+        image.data[0] = 0.1;
+
+        let mut buffer = Vec::new();
+        image.write_into(&mut buffer).unwrap();
+        assert!(buffer.starts_with(b"PD\n1 2\n-1\n"));
+        assert_eq!(buffer.len(), 10 + 6 * 8);
+        // The bottom row is stored first.
+        assert_eq!(&buffer[10..18], &4.0f64.to_le_bytes());
+
+        let decoded = Pfm64::from_bytes(&buffer).unwrap();
+        assert_eq!(decoded, image);
+        assert_eq!(decoded.to_pfm().unwrap().data[0], 0.1f32);
+
+        assert!(Pfm64::from_bytes(b"PF\n1 1\n-1\n\0\0\0\0").is_err());
+        assert!(Pfm64::from_bytes(&buffer[..buffer.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_pfm64_bottom_up() {
+        let mut pfm = PFM::from_fn(2, 3, false, |x, y, _| (x + 10 * y) as f32);
+        pfm.set_row_order(RowOrder::BottomToTop);
+
+        let image = Pfm64::from_pfm(&pfm);
+        pfm.set_row_order(RowOrder::TopToBottom);
+        assert_eq!(image.to_pfm().unwrap(), pfm);
+    }
+}
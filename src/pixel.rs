@@ -0,0 +1,327 @@
+use crate::common::{f32_as_bytes, f32_as_bytes_mut};
+use crate::pfm::PFM;
+use std::ops::{Index, IndexMut};
+use std::slice::{self, ChunksExact, ChunksExactMut};
+
+/// Value of a single pixel.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Pixel {
+    Mono(f32),
+    Rgb([f32; 3]),
+}
+
+impl Pixel {
+    /// Channel values of the pixel.
+    pub fn as_slice(&self) -> &[f32] {
+        match self {
+            Pixel::Mono(v) => std::slice::from_ref(v),
+            Pixel::Rgb(v) => v,
+        }
+    }
+}
+
+impl PFM {
+    /// Get the pixel at column `x` and row `y` (from the top), or `None` if
+    /// it's out of bounds.
+    pub fn get(&self, x: usize, y: usize) -> Option<Pixel> {
+        let offset = self.pixel_offset(x, y)?;
+        let pixel = self.data.get(offset..offset + self.num_channels())?;
+
+        if self.color {
+            Some(Pixel::Rgb([pixel[0], pixel[1], pixel[2]]))
+        } else {
+            Some(Pixel::Mono(pixel[0]))
+        }
+    }
+
+    /// Set the pixel at column `x` and row `y` (from the top). The pixel must
+    /// be in bounds and match the color mode of the image.
+    pub fn set(&mut self, x: usize, y: usize, pixel: Pixel) -> Result<(), &'static str> {
+        let offset = match self.pixel_offset(x, y) {
+            Some(offset) => offset,
+            None => return Err("The pixel is out of the image bounds"),
+        };
+
+        let values = pixel.as_slice();
+        if values.len() != self.num_channels() {
+            return Err("The pixel does not match the color mode of the image");
+        }
+
+        match self.data.get_mut(offset..offset + values.len()) {
+            Some(dst) => dst.copy_from_slice(values),
+            None => return Err("The pixel is out of the image bounds"),
+        }
+
+        Ok(())
+    }
+
+    /// Iterate over the rows from top to bottom, each of `width * channels`
+    /// values.
+    pub fn rows(&self) -> ChunksExact<'_, f32> {
+        let row_len = self.width * self.num_channels();
+        self.data.chunks_exact(row_len.max(1))
+    }
+
+    /// Iterate mutably over the rows from top to bottom, each of
+    /// `width * channels` values.
+    pub fn rows_mut(&mut self) -> ChunksExactMut<'_, f32> {
+        let row_len = self.width * self.num_channels();
+        self.data.chunks_exact_mut(row_len.max(1))
+    }
+
+    /// Iterate over the pixels from top to bottom, left to right, yielding
+    /// their column, row and channel values.
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize, &[f32])> + '_ {
+        let width = self.width;
+        self.data
+            .chunks_exact(self.num_channels())
+            .enumerate()
+            .map(move |(i, pixel)| (i % width, i / width, pixel))
+    }
+
+    /// Iterate mutably over the pixels from top to bottom, left to right,
+    /// yielding their column, row and channel values.
+    pub fn pixels_mut(&mut self) -> impl Iterator<Item = (usize, usize, &mut [f32])> + '_ {
+        let width = self.width;
+        let num_channels = self.num_channels();
+        self.data
+            .chunks_exact_mut(num_channels)
+            .enumerate()
+            .map(move |(i, pixel)| (i % width, i / width, pixel))
+    }
+
+    /// View the data of a color image as RGB pixels.
+    pub fn as_rgb(&self) -> Result<&[[f32; 3]], &'static str> {
+        self.check_data_shape(true)?;
+
+        let len = self.data.len() / 3;
+        // SAFETY: `[f32; 3]` has the layout of three consecutive `f32`s and
+        // the shape check guarantees that the length is a multiple of 3.
+        Ok(unsafe { slice::from_raw_parts(self.data.as_ptr() as *const [f32; 3], len) })
+    }
+
+    /// View the data of a color image as mutable RGB pixels.
+    pub fn as_rgb_mut(&mut self) -> Result<&mut [[f32; 3]], &'static str> {
+        self.check_data_shape(true)?;
+
+        let len = self.data.len() / 3;
+        // SAFETY: as in `as_rgb`.
+        Ok(unsafe { slice::from_raw_parts_mut(self.data.as_mut_ptr() as *mut [f32; 3], len) })
+    }
+
+    /// View the data of a monochrome image as luminance values.
+    pub fn as_luma(&self) -> Result<&[f32], &'static str> {
+        self.check_data_shape(false)?;
+
+        Ok(&self.data)
+    }
+
+    /// View the data of a monochrome image as mutable luminance values.
+    pub fn as_luma_mut(&mut self) -> Result<&mut [f32], &'static str> {
+        self.check_data_shape(false)?;
+
+        Ok(&mut self.data)
+    }
+
+    /// View the pixel values as bytes in the host endianness, without copying.
+    pub fn as_bytes(&self) -> &[u8] {
+        f32_as_bytes(&self.data)
+    }
+
+    /// View the pixel values as mutable bytes in the host endianness, without
+    /// copying.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        f32_as_bytes_mut(&mut self.data)
+    }
+
+    fn check_data_shape(&self, color: bool) -> Result<(), &'static str> {
+        if self.color != color {
+            return Err("The image does not have the requested color mode");
+        }
+
+        if self.data.len() != self.width * self.height * self.num_channels() {
+            return Err("Length of data does not match the image size");
+        }
+
+        Ok(())
+    }
+
+    /// Offset of the first channel of pixel (`x`, `y`) in `data`.
+    pub(crate) fn pixel_offset(&self, x: usize, y: usize) -> Option<usize> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        Some((y * self.width + x) * self.num_channels())
+    }
+}
+
+impl Index<(usize, usize)> for PFM {
+    type Output = [f32];
+
+    /// Get the channel values of pixel (`x`, `y`). Panics if the pixel is out
+    /// of bounds.
+    fn index(&self, (x, y): (usize, usize)) -> &[f32] {
+        let offset = match self.pixel_offset(x, y) {
+            Some(offset) => offset,
+            None => panic!("Pixel ({}, {}) is out of the image bounds", x, y),
+        };
+
+        &self.data[offset..offset + self.num_channels()]
+    }
+}
+
+impl IndexMut<(usize, usize)> for PFM {
+    /// Get the channel values of pixel (`x`, `y`) mutably. Panics if the pixel
+    /// is out of bounds.
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut [f32] {
+        let offset = match self.pixel_offset(x, y) {
+            Some(offset) => offset,
+            None => panic!("Pixel ({}, {}) is out of the image bounds", x, y),
+        };
+        let num_channels = self.num_channels();
+
+        &mut self.data[offset..offset + num_channels]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+
+    #[test]
+    fn test_get_set() {
+        let mut pfm = PFMBuilder::new()
+            .color(true)
+            .size(2, 1)
+            .data(vec![0.0, 0.1, 0.2, 1.0, 1.1, 1.2])
+            .build()
+            .unwrap();
+
+        assert_eq!(pfm.get(1, 0), Some(Pixel::Rgb([1.0, 1.1, 1.2])));
+        assert_eq!(pfm.get(2, 0), None);
+        assert_eq!(pfm.get(0, 1), None);
+
+        pfm.set(0, 0, Pixel::Rgb([5.0, 6.0, 7.0])).unwrap();
+        assert_eq!(pfm.data, vec![5.0, 6.0, 7.0, 1.0, 1.1, 1.2]);
+        assert!(pfm.set(0, 0, Pixel::Mono(1.0)).is_err());
+        assert!(pfm.set(0, 1, Pixel::Rgb([0.0; 3])).is_err());
+    }
+
+    #[test]
+    fn test_index() {
+        let mut pfm = PFMBuilder::new()
+            .color(false)
+            .size(2, 2)
+            .data(vec![1.0, 2.0, 3.0, 4.0])
+            .build()
+            .unwrap();
+
+        assert_eq!(pfm[(1, 0)], [2.0]);
+        assert_eq!(pfm[(0, 1)][0], 3.0);
+
+        pfm[(1, 1)][0] = 8.0;
+        assert_eq!(pfm.data, vec![1.0, 2.0, 3.0, 8.0]);
+    }
+
+    #[test]
+    fn test_rows() {
+        let mut pfm = PFMBuilder::new()
+            .color(true)
+            .size(1, 2)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .build()
+            .unwrap();
+
+        let rows: Vec<_> = pfm.rows().collect();
+        assert_eq!(rows, vec![&[1.0, 2.0, 3.0][..], &[4.0, 5.0, 6.0]]);
+
+        for row in pfm.rows_mut() {
+            row.reverse();
+        }
+        assert_eq!(pfm.data, vec![3.0, 2.0, 1.0, 6.0, 5.0, 4.0]);
+
+        assert_eq!(PFMBuilder::new().build().unwrap().rows().count(), 0);
+    }
+
+    #[test]
+    fn test_pixels() {
+        let mut pfm = PFMBuilder::new()
+            .color(false)
+            .size(2, 2)
+            .data(vec![1.0, 2.0, 3.0, 4.0])
+            .build()
+            .unwrap();
+
+        let pixels: Vec<_> = pfm.pixels().collect();
+        assert_eq!(
+            pixels,
+            vec![
+                (0, 0, &[1.0][..]),
+                (1, 0, &[2.0]),
+                (0, 1, &[3.0]),
+                (1, 1, &[4.0])
+            ]
+        );
+
+        for (x, y, pixel) in pfm.pixels_mut() {
+            pixel[0] = (x + 10 * y) as f32;
+        }
+        assert_eq!(pfm.data, vec![0.0, 1.0, 10.0, 11.0]);
+    }
+
+    #[test]
+    fn test_as_rgb_and_luma() {
+        let mut pfm = PFMBuilder::new()
+            .color(true)
+            .size(2, 1)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .build()
+            .unwrap();
+
+        assert_eq!(pfm.as_rgb().unwrap(), &[[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]);
+        pfm.as_rgb_mut().unwrap()[1][0] = 0.0;
+        assert_eq!(pfm.data[3], 0.0);
+        assert!(pfm.as_luma().is_err());
+
+        pfm.data.pop();
+        assert!(pfm.as_rgb().is_err());
+
+        let mut mono = pfm.channel(0).unwrap();
+        assert_eq!(mono.as_luma().unwrap(), &[1.0, 0.0]);
+        mono.as_luma_mut().unwrap()[0] = 2.0;
+        assert_eq!(mono.data, vec![2.0, 0.0]);
+        assert!(mono.as_rgb().is_err());
+    }
+
+    #[test]
+    fn test_as_bytes() {
+        let mut pfm = PFMBuilder::new()
+            .color(false)
+            .size(2, 1)
+            .data(vec![1.0, -2.5])
+            .build()
+            .unwrap();
+
+        let mut expected = 1.0f32.to_ne_bytes().to_vec();
+        expected.extend_from_slice(&(-2.5f32).to_ne_bytes());
+        assert_eq!(pfm.as_bytes(), &expected[..]);
+
+        pfm.as_bytes_mut()[4..].copy_from_slice(&3.0f32.to_ne_bytes());
+        assert_eq!(pfm.data, vec![1.0, 3.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_bounds() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .size(1, 1)
+            .data(vec![1.0])
+            .build()
+            .unwrap();
+
+        let _ = pfm[(1, 0)][0];
+    }
+}
@@ -0,0 +1,204 @@
+use crate::common::{try_vec, RowOrder};
+use crate::options::PfmWriteOptions;
+use crate::pfm::{encode_rows_with, PfmHeader, PFM};
+use crate::stream::PfmReader;
+use crate::unit::{comments_with_unit, take_unit, Unit};
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+/// A PFM image whose pixel values are stored in memory in planar layout, i.e.
+/// all values of the first channel, then all values of the second channel and
+/// so on. Values are converted from and to the interleaved layout of the file
+/// row by row while reading and writing.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlanarPfm {
+    pub header: PfmHeader,
+    /// Planes of width * height values each, whose pixels are in the top to
+    /// bottom, left to right order.
+    pub data: Vec<f32>,
+    /// Physical unit of the pixel values, if known.
+    pub unit: Option<Unit>,
+    /// Lines of the `#` comments of the header.
+    pub comments: Vec<String>,
+}
+
+impl PlanarPfm {
+    /// Convert the interleaved values of `pfm` into planes. Rows are
+    /// reordered from top to bottom if needed.
+    pub fn from_pfm(pfm: &PFM) -> PlanarPfm {
+        let num_channels = pfm.num_channels();
+        let plane_len = pfm.width * pfm.height;
+
+        let mut data = vec![0.0; pfm.data.len()];
+        for (i, pixel) in pfm.data.chunks(num_channels).enumerate() {
+            let i = match pfm.row_order {
+                RowOrder::TopToBottom => i,
+                RowOrder::BottomToTop => {
+                    (pfm.height - 1 - i / pfm.width) * pfm.width + i % pfm.width
+                }
+            };
+            for (c, &v) in pixel.iter().enumerate() {
+                data[c * plane_len + i] = v;
+            }
+        }
+
+        PlanarPfm {
+            header: pfm.header(),
+            data,
+            unit: pfm.unit,
+            comments: pfm.comments.clone(),
+        }
+    }
+
+    /// Convert the planes back into interleaved values, in top to bottom
+    /// order.
+    pub fn to_pfm(&self) -> Result<PFM, &'static str> {
+        let num_channels = self.header.num_channels();
+        let plane_len = self.header.width * self.header.height;
+        if self.data.len() != plane_len * num_channels {
+            return Err("The length of image data is not equal to width * height * channels specified in the header");
+        }
+
+        let mut data = vec![0.0; self.data.len()];
+        for (c, plane) in self.data.chunks(plane_len).enumerate() {
+            for (i, &v) in plane.iter().enumerate() {
+                data[i * num_channels + c] = v;
+            }
+        }
+
+        let mut pfm = PFM::from_header(&self.header, data)?;
+        pfm.unit = self.unit;
+        pfm.comments = self.comments.clone();
+
+        Ok(pfm)
+    }
+
+    /// Get the plane of channel `c`, or `None` if it's out of bounds.
+    pub fn plane(&self, c: usize) -> Option<&[f32]> {
+        self.data
+            .chunks(self.plane_len())
+            .take(self.header.num_channels())
+            .nth(c)
+    }
+
+    /// Get one mutable slice per channel, each of length width * height.
+    pub fn planes_mut(&mut self) -> Vec<&mut [f32]> {
+        let plane_len = self.plane_len();
+        self.data.chunks_mut(plane_len).collect()
+    }
+
+    /// Read a PFM file, splitting each row into the planes.
+    pub fn read_from(reader: &mut impl Read) -> Result<PlanarPfm, &'static str> {
+        let mut reader = PfmReader::new(reader)?;
+        let header = *reader.header();
+        let mut comments = reader.comments().to_vec();
+        let unit = take_unit(&mut comments);
+        let num_channels = header.num_channels();
+        let plane_len = header.width * header.height;
+
+        let mut data = try_vec(plane_len * num_channels, 0.0)?;
+        let mut row = try_vec(header.row_len(), 0.0)?;
+        while let Some(y) = reader.read_row(&mut row)? {
+            for (x, pixel) in row.chunks(num_channels).enumerate() {
+                for (c, &v) in pixel.iter().enumerate() {
+                    data[c * plane_len + y * header.width + x] = v;
+                }
+            }
+        }
+
+        Ok(PlanarPfm {
+            header,
+            data,
+            unit,
+            comments,
+        })
+    }
+
+    /// Write the image as a PFM file, interleaving each row.
+    pub fn write_into(&self, writer: &mut impl Write) -> Result<(), &'static str> {
+        let (width, height) = (self.header.width, self.header.height);
+        let num_channels = self.header.num_channels();
+        if width == 0 || height == 0 {
+            return Err("Invalid width or height");
+        }
+
+        let plane_len = width * height;
+        if self.data.len() != plane_len * num_channels {
+            return Err("The length of image data is not equal to width * height * channels specified in the header");
+        }
+
+        let mut writer = BufWriter::new(writer);
+        let comments = comments_with_unit(&self.comments, self.unit);
+        let options = &mut PfmWriteOptions::new();
+        encode_rows_with(&self.header, &comments, &mut writer, options, |i, row| {
+            let y = height - 1 - i;
+            for (x, pixel) in row.chunks_mut(num_channels).enumerate() {
+                for (c, v) in pixel.iter_mut().enumerate() {
+                    *v = self.data[c * plane_len + y * width + x];
+                }
+            }
+        })?;
+
+        match writer.flush() {
+            Ok(_) => Ok(()),
+            Err(_) => Err("Unable to write into the writer"),
+        }
+    }
+
+    fn plane_len(&self) -> usize {
+        (self.header.width * self.header.height).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_planar() {
+        let pfm = PFMBuilder::new()
+            .color(true)
+            .size(2, 2)
+            .data((0..12).map(|v| v as f32).collect())
+            .build()
+            .unwrap();
+
+        let mut planar = PlanarPfm::from_pfm(&pfm);
+        assert_eq!(planar.plane(1), Some(&[1.0, 4.0, 7.0, 10.0][..]));
+        assert_eq!(planar.plane(3), None);
+        assert_eq!(planar.to_pfm().unwrap(), pfm);
+
+        let mut buffer = Vec::new();
+        planar.write_into(&mut buffer).unwrap();
+        assert_eq!(buffer, pfm.to_bytes().unwrap());
+        assert_eq!(
+            PlanarPfm::read_from(&mut Cursor::new(&buffer)).unwrap(),
+            planar
+        );
+
+        planar.planes_mut()[2].fill(0.0);
+        assert_eq!(planar.to_pfm().unwrap().channel(2).unwrap().data, [0.0; 4]);
+    }
+
+    #[test]
+    fn test_planar_bottom_up() {
+        let mut pfm = PFM::from_fn(2, 3, true, |x, y, c| (x + 10 * y + 100 * c) as f32);
+        pfm.comments = vec!["made by test".to_string()];
+        pfm.unit = Some(Unit::Meters);
+        pfm.set_row_order(RowOrder::BottomToTop);
+
+        let planar = PlanarPfm::from_pfm(&pfm);
+        let mut buffer = Vec::new();
+        planar.write_into(&mut buffer).unwrap();
+        assert_eq!(buffer, pfm.to_bytes().unwrap());
+        assert_eq!(
+            PlanarPfm::read_from(&mut Cursor::new(&buffer)).unwrap(),
+            planar
+        );
+
+        pfm.set_row_order(RowOrder::TopToBottom);
+        assert_eq!(planar.to_pfm().unwrap(), pfm);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::pfm::PFM;
+#[cfg(feature = "ply")]
+use byteorder::{LittleEndian, WriteBytesExt};
+#[cfg(feature = "ply")]
+use std::io::prelude::*;
+
+/// Pinhole camera intrinsics in pixels.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Intrinsics {
+    pub fx: f32,
+    pub fy: f32,
+    pub cx: f32,
+    pub cy: f32,
+}
+
+impl PFM {
+    /// Back-project a monochrome depth map into camera space, with x to the
+    /// right, y down and z forward. Pixels with non-finite or non-positive
+    /// depth are skipped.
+    pub fn to_point_cloud(&self, intrinsics: Intrinsics) -> Result<Vec<[f32; 3]>, &'static str> {
+        if self.color {
+            return Err("Only monochrome images can be converted to point clouds");
+        }
+
+        let points = self
+            .pixels()
+            .map(|(x, y, pixel)| (x, y, pixel[0] * self.scale_factor))
+            .filter(|&(_, _, z)| z.is_finite() && z > 0.0)
+            .map(|(x, y, z)| {
+                [
+                    (x as f32 - intrinsics.cx) * z / intrinsics.fx,
+                    (y as f32 - intrinsics.cy) * z / intrinsics.fy,
+                    z,
+                ]
+            })
+            .collect();
+
+        Ok(points)
+    }
+}
+
+/// Write `points` as a binary little endian PLY file.
+#[cfg(feature = "ply")]
+pub fn write_ply(writer: &mut impl Write, points: &[[f32; 3]]) -> Result<(), &'static str> {
+    let header = format!(
+        "ply\nformat binary_little_endian 1.0\nelement vertex {}\n\
+         property float x\nproperty float y\nproperty float z\nend_header\n",
+        points.len()
+    );
+
+    let mut writer = std::io::BufWriter::new(writer);
+    if writer.write_all(header.as_bytes()).is_err() {
+        return Err("Unable to write ply header");
+    }
+
+    for point in points {
+        for &v in point {
+            if writer.write_f32::<LittleEndian>(v).is_err() {
+                return Err("Unable to write ply vertices");
+            }
+        }
+    }
+
+    match writer.flush() {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Unable to write ply vertices"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+
+    #[test]
+    fn test_to_point_cloud() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .size(2, 2)
+            .data(vec![2.0, f32::INFINITY, 0.0, 4.0])
+            .build()
+            .unwrap();
+        let intrinsics = Intrinsics {
+            fx: 2.0,
+            fy: 4.0,
+            cx: 0.0,
+            cy: 0.0,
+        };
+
+        let points = pfm.to_point_cloud(intrinsics).unwrap();
+        assert_eq!(points, vec![[0.0, 0.0, 2.0], [2.0, 1.0, 4.0]]);
+    }
+
+    #[cfg(feature = "ply")]
+    #[test]
+    fn test_write_ply() {
+        let mut buffer = Vec::new();
+        write_ply(&mut buffer, &[[1.0, 2.0, 3.0]]).unwrap();
+
+        let header = b"ply\nformat binary_little_endian 1.0\nelement vertex 1\n";
+        assert!(buffer.starts_with(header));
+        assert!(buffer.ends_with(&3.0f32.to_le_bytes()));
+        let end = b"end_header\n";
+        let payload = buffer.windows(end.len()).position(|w| w == end).unwrap() + end.len();
+        assert_eq!(buffer.len() - payload, 12);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::common::{Endian, RowOrder};
+use crate::pfm::PFM;
+use std::io::prelude::*;
+use std::sync::Mutex;
+
+/// A pool of pixel buffers for loading many images of the same resolution,
+/// so that each load reuses the buffer of an image recycled earlier instead
+/// of allocating a new one. The pool can be shared between threads.
+#[derive(Debug)]
+pub struct PfmPool {
+    len: usize,
+    max_buffers: usize,
+    buffers: Mutex<Vec<Vec<f32>>>,
+}
+
+impl PfmPool {
+    /// Create an empty pool for `width` x `height` images, keeping at most
+    /// `max_buffers` idle buffers.
+    pub fn new(width: usize, height: usize, color: bool, max_buffers: usize) -> PfmPool {
+        let num_channels = if color { 3 } else { 1 };
+
+        PfmPool {
+            len: width * height * num_channels,
+            max_buffers,
+            buffers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Read a PFM file like `PFM::read_from`, into a buffer of the pool.
+    /// Files of other resolutions are read as well, at the cost of an
+    /// allocation.
+    pub fn load(&self, reader: &mut impl Read) -> Result<PFM, &'static str> {
+        let mut pfm = PFM {
+            width: 0,
+            height: 0,
+            color: false,
+            scale_factor: 1.0,
+            endian: Endian::host(),
+            data: self.take(),
+            unit: None,
+            comments: Vec::new(),
+            row_order: RowOrder::TopToBottom,
+        };
+
+        match PFM::read_into(reader, &mut pfm) {
+            Ok(()) => Ok(pfm),
+            Err(e) => {
+                self.recycle(pfm);
+                Err(e)
+            }
+        }
+    }
+
+    /// Give the buffer of `pfm` back to the pool. Buffers too small for the
+    /// resolution of the pool are dropped, as are buffers beyond
+    /// `max_buffers`.
+    pub fn recycle(&self, pfm: PFM) {
+        if pfm.data.capacity() < self.len {
+            return;
+        }
+
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < self.max_buffers {
+            buffers.push(pfm.data);
+        }
+    }
+
+    /// Number of idle buffers in the pool.
+    pub fn idle(&self) -> usize {
+        self.buffers.lock().unwrap().len()
+    }
+
+    fn take(&self) -> Vec<f32> {
+        match self.buffers.lock().unwrap().pop() {
+            Some(buffer) => buffer,
+            None => Vec::with_capacity(self.len),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pfm_pool() {
+        let frame = PFM::from_fn(3, 2, true, |x, y, c| (x + y + c) as f32);
+        let buffer = frame.to_bytes().unwrap();
+        let pool = PfmPool::new(3, 2, true, 1);
+
+        let first = pool.load(&mut buffer.as_slice()).unwrap();
+        assert_eq!(first, frame);
+        let ptr = first.data.as_ptr();
+        pool.recycle(first);
+        assert_eq!(pool.idle(), 1);
+
+        let second = pool.load(&mut buffer.as_slice()).unwrap();
+        assert_eq!(second.data.as_ptr(), ptr);
+        assert_eq!(pool.idle(), 0);
+
+        pool.recycle(second);
+        pool.recycle(frame.clone());
+        assert_eq!(pool.idle(), 1);
+        pool.recycle(PFM::new_filled(1, 1, false, 0.0));
+        assert_eq!(pool.idle(), 1);
+
+        assert!(pool.load(&mut &buffer[..10]).is_err());
+        assert_eq!(pool.idle(), 1);
+    }
+}
@@ -0,0 +1,170 @@
+//! Temporal reductions over sequences of same-sized `PFM` frames.
+//!
+//! `mean` and `max` accumulate frame by frame, so only one frame and the
+//! accumulator are held in memory at a time. The result takes the scaling
+//! factor, endianness and unit of the first frame, and the other frames are
+//! rescaled to that scaling factor first.
+use crate::pfm::PFM;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+
+/// Per-pixel mean of all frames.
+pub fn mean<I>(frames: I) -> Result<PFM, &'static str>
+where
+    I: IntoIterator,
+    I::Item: Borrow<PFM>,
+{
+    let mut frames = frames.into_iter();
+    let first = match frames.next() {
+        Some(frame) => frame.borrow().clone(),
+        None => return Err("The sequence contains no frames"),
+    };
+
+    let mut sum: Vec<f64> = first.data.iter().map(|&v| f64::from(v)).collect();
+    let mut count = 1usize;
+
+    for frame in frames {
+        let frame = frame.borrow();
+        check_same_shape(&first, frame)?;
+        let ratio = f64::from(frame.scale_factor / first.scale_factor);
+        for (s, &v) in sum.iter_mut().zip(frame.data.iter()) {
+            *s += f64::from(v) * ratio;
+        }
+        count += 1;
+    }
+
+    let data = sum.iter().map(|s| (s / count as f64) as f32).collect();
+
+    Ok(first.with_data(first.width, first.height, first.color, data))
+}
+
+/// Per-pixel maximum of all frames. NaN values are ignored unless all frames
+/// are NaN at that position.
+pub fn max<I>(frames: I) -> Result<PFM, &'static str>
+where
+    I: IntoIterator,
+    I::Item: Borrow<PFM>,
+{
+    let mut frames = frames.into_iter();
+    let mut result = match frames.next() {
+        Some(frame) => frame.borrow().clone(),
+        None => return Err("The sequence contains no frames"),
+    };
+
+    for frame in frames {
+        let frame = frame.borrow();
+        check_same_shape(&result, frame)?;
+        let ratio = frame.scale_factor / result.scale_factor;
+        for (m, &v) in result.data.iter_mut().zip(frame.data.iter()) {
+            *m = m.max(v * ratio);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Per-pixel median of all frames. For an even number of frames the mean of
+/// the two middle values is used.
+///
+/// Unlike `mean` and `max`, the exact median needs every frame, so all
+/// frames are kept in memory.
+pub fn median<I>(frames: I) -> Result<PFM, &'static str>
+where
+    I: IntoIterator,
+    I::Item: Borrow<PFM>,
+{
+    let mut frames = frames.into_iter();
+    let first = match frames.next() {
+        Some(frame) => frame.borrow().clone(),
+        None => return Err("The sequence contains no frames"),
+    };
+
+    let mut others = Vec::new();
+    for frame in frames {
+        let frame = frame.borrow();
+        check_same_shape(&first, frame)?;
+        let ratio = frame.scale_factor / first.scale_factor;
+        others.push(frame.data.iter().map(|v| v * ratio).collect::<Vec<f32>>());
+    }
+
+    let cmp = |a: &f32, b: &f32| a.partial_cmp(b).unwrap_or(Ordering::Equal);
+    let len = others.len() + 1;
+    let mut samples = Vec::with_capacity(len);
+    let mid = len / 2;
+    let data = (0..first.data.len())
+        .map(|i| {
+            samples.clear();
+            samples.push(first.data[i]);
+            samples.extend(others.iter().map(|frame| frame[i]));
+
+            let (lower, median, _) = samples.select_nth_unstable_by(mid, cmp);
+            if len % 2 == 0 {
+                let below = lower.iter().copied().max_by(cmp).unwrap_or(*median);
+                (below + *median) / 2.0
+            } else {
+                *median
+            }
+        })
+        .collect();
+
+    Ok(first.with_data(first.width, first.height, first.color, data))
+}
+
+fn check_same_shape(a: &PFM, b: &PFM) -> Result<(), &'static str> {
+    if a.width != b.width || a.height != b.height || a.color != b.color {
+        return Err("All frames must have the same size and color mode");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::{mono, PFMBuilder};
+
+    #[test]
+    fn test_reductions() {
+        let frames = vec![
+            mono(2, 1, vec![1.0, 4.0]),
+            mono(2, 1, vec![3.0, -2.0]),
+            mono(2, 1, vec![8.0, 1.0]),
+        ];
+
+        assert_eq!(mean(&frames).unwrap().data, vec![4.0, 1.0]);
+        assert_eq!(max(&frames).unwrap().data, vec![8.0, 4.0]);
+        assert_eq!(median(&frames).unwrap().data, vec![3.0, 1.0]);
+        assert_eq!(
+            median(frames.into_iter().skip(1)).unwrap().data,
+            vec![5.5, -0.5]
+        );
+    }
+
+    #[test]
+    fn test_reductions_rescale_frames() {
+        let mut scaled = mono(2, 1, vec![2.0, 8.0]);
+        scaled.scale_factor = 0.5;
+        let frames = vec![
+            mono(2, 1, vec![1.0, 4.0]),
+            scaled,
+            mono(2, 1, vec![3.0, 0.0]),
+        ];
+
+        assert_eq!(mean(&frames).unwrap().data, vec![5.0 / 3.0, 8.0 / 3.0]);
+        assert_eq!(max(&frames).unwrap().data, vec![3.0, 4.0]);
+        assert_eq!(median(&frames).unwrap().data, vec![1.0, 4.0]);
+    }
+
+    #[test]
+    fn test_reductions_reject_invalid_sequences() {
+        assert!(mean(Vec::<PFM>::new()).is_err());
+
+        let other = PFMBuilder::new()
+            .color(false)
+            .size(1, 2)
+            .data(vec![0.0, 0.0])
+            .build()
+            .unwrap();
+        assert!(max(vec![mono(2, 1, vec![0.0, 0.0]), other]).is_err());
+    }
+}
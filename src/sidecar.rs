@@ -0,0 +1,422 @@
+use crate::PXM;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A JSON value of the sidecar metadata of `PXM::save_with_metadata`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum MetadataValue {
+    Null,
+    Bool(bool),
+    /// Non-finite numbers are written as `null`.
+    Number(f64),
+    String(String),
+    Array(Vec<MetadataValue>),
+    Object(Metadata),
+}
+
+/// Metadata stored in a JSON object next to an image.
+pub type Metadata = BTreeMap<String, MetadataValue>;
+
+impl From<bool> for MetadataValue {
+    fn from(v: bool) -> MetadataValue {
+        MetadataValue::Bool(v)
+    }
+}
+
+impl From<f64> for MetadataValue {
+    fn from(v: f64) -> MetadataValue {
+        MetadataValue::Number(v)
+    }
+}
+
+impl From<&str> for MetadataValue {
+    fn from(v: &str) -> MetadataValue {
+        MetadataValue::String(v.to_string())
+    }
+}
+
+impl From<String> for MetadataValue {
+    fn from(v: String) -> MetadataValue {
+        MetadataValue::String(v)
+    }
+}
+
+impl PXM {
+    /// Save the image like `save`, and `metadata` as JSON into `<path>.json`,
+    /// e.g. `depth.pfm.json`.
+    pub fn save_with_metadata(
+        &self,
+        path: impl AsRef<Path>,
+        metadata: &Metadata,
+    ) -> Result<(), &'static str> {
+        let path = path.as_ref();
+        if path == Path::new("-") {
+            return Err("Unable to write a sidecar for stdout");
+        }
+
+        self.save(path)?;
+
+        let mut json = String::new();
+        write_object(&mut json, metadata);
+        json.push('\n');
+        match fs::write(sidecar_path(path), json) {
+            Ok(_) => Ok(()),
+            Err(_) => Err("Unable to write the metadata sidecar"),
+        }
+    }
+
+    /// Load the image like `load`, together with the metadata in
+    /// `<path>.json`. The metadata is empty if there's no sidecar.
+    pub fn load_with_metadata(path: impl AsRef<Path>) -> Result<(PXM, Metadata), &'static str> {
+        let path = path.as_ref();
+        let pxm = PXM::load(path)?;
+
+        let json = match fs::read_to_string(sidecar_path(path)) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((pxm, Metadata::new())),
+            Err(_) => return Err("Unable to read the metadata sidecar"),
+        };
+
+        let mut parser = Parser::new(&json);
+        let metadata = match parser.value()? {
+            MetadataValue::Object(metadata) => metadata,
+            _ => return Err("The metadata sidecar must hold a JSON object"),
+        };
+        parser.skip_whitespace();
+        if parser.pos != parser.input.len() {
+            return Err("Trailing characters in the metadata sidecar");
+        }
+
+        Ok((pxm, metadata))
+    }
+}
+
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".json");
+    PathBuf::from(sidecar)
+}
+
+fn write_value(out: &mut String, value: &MetadataValue) {
+    match value {
+        MetadataValue::Null => out.push_str("null"),
+        MetadataValue::Bool(v) => out.push_str(if *v { "true" } else { "false" }),
+        MetadataValue::Number(v) if v.is_finite() => {
+            let _ = write!(out, "{}", v);
+        }
+        MetadataValue::Number(_) => out.push_str("null"),
+        MetadataValue::String(s) => write_string(out, s),
+        MetadataValue::Array(values) => {
+            out.push('[');
+            for (i, v) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_value(out, v);
+            }
+            out.push(']');
+        }
+        MetadataValue::Object(object) => write_object(out, object),
+    }
+}
+
+fn write_object(out: &mut String, object: &Metadata) {
+    out.push('{');
+    for (i, (k, v)) in object.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_string(out, k);
+        out.push_str(": ");
+        write_value(out, v);
+    }
+    out.push('}');
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Maximum nesting depth of arrays and objects, to bound the recursion of
+/// `Parser` on untrusted sidecars.
+const MAX_DEPTH: usize = 128;
+
+/// Recursive descent parser of JSON text.
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(json: &'a str) -> Parser<'a> {
+        Parser {
+            input: json.as_bytes(),
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn value(&mut self) -> Result<MetadataValue, &'static str> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.nested(Parser::object),
+            Some(b'[') => self.nested(Parser::array),
+            Some(b'"') => self.string().map(MetadataValue::String),
+            Some(b't') => self.literal("true", MetadataValue::Bool(true)),
+            Some(b'f') => self.literal("false", MetadataValue::Bool(false)),
+            Some(b'n') => self.literal("null", MetadataValue::Null),
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            _ => Err("Invalid JSON value"),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Parser<'a>) -> Result<MetadataValue, &'static str>,
+    ) -> Result<MetadataValue, &'static str> {
+        if self.depth == MAX_DEPTH {
+            return Err("The metadata sidecar is nested too deeply");
+        }
+
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+
+        value
+    }
+
+    fn object(&mut self) -> Result<MetadataValue, &'static str> {
+        self.pos += 1;
+        let mut object = Metadata::new();
+        self.skip_whitespace();
+        if self.eat(b'}') {
+            return Ok(MetadataValue::Object(object));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err("Expected a JSON object key");
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(b':') {
+                return Err("Expected ':' in a JSON object");
+            }
+            let value = self.value()?;
+            object.insert(key, value);
+
+            self.skip_whitespace();
+            if self.eat(b'}') {
+                return Ok(MetadataValue::Object(object));
+            }
+            if !self.eat(b',') {
+                return Err("Expected ',' or '}' in a JSON object");
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<MetadataValue, &'static str> {
+        self.pos += 1;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.eat(b']') {
+            return Ok(MetadataValue::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            if self.eat(b']') {
+                return Ok(MetadataValue::Array(values));
+            }
+            if !self.eat(b',') {
+                return Err("Expected ',' or ']' in a JSON array");
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, &'static str> {
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let b = match self.next() {
+                Some(b) => b,
+                None => return Err("Unterminated JSON string"),
+            };
+
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let c = match self.next() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.unicode_escape()?,
+                        _ => return Err("Invalid escape in a JSON string"),
+                    };
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                b if b < 0x20 => return Err("Control character in a JSON string"),
+                b => bytes.push(b),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| "Invalid UTF-8 in a JSON string")
+    }
+
+    /// Decode the hex digits after `\u`, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char, &'static str> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if !(self.eat(b'\\') && self.eat(b'u')) {
+                return Err("Unpaired surrogate in a JSON string");
+            }
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err("Unpaired surrogate in a JSON string");
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+
+        char::from_u32(code).ok_or("Invalid unicode escape in a JSON string")
+    }
+
+    fn hex4(&mut self) -> Result<u32, &'static str> {
+        let digits = self.input.get(self.pos..self.pos + 4);
+        let code = digits
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok());
+        self.pos += 4;
+
+        code.ok_or("Invalid unicode escape in a JSON string")
+    }
+
+    fn number(&mut self) -> Result<MetadataValue, &'static str> {
+        let start = self.pos;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+
+        std::str::from_utf8(&self.input[start..self.pos])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(MetadataValue::Number)
+            .ok_or("Invalid JSON number")
+    }
+
+    fn literal(&mut self, text: &str, value: MetadataValue) -> Result<MetadataValue, &'static str> {
+        if self.input[self.pos..].starts_with(text.as_bytes()) {
+            self.pos += text.len();
+            Ok(value)
+        } else {
+            Err("Invalid JSON value")
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        if self.peek() == Some(b) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFM;
+    use std::env;
+
+    #[test]
+    fn test_sidecar_round_trip() {
+        let mut path = env::temp_dir();
+        path.push("pxm_sidecar_test.pfm");
+        let pxm = PXM::from(PFM::new_filled(2, 1, false, 0.5));
+
+        let mut camera = Metadata::new();
+        camera.insert(String::from("baseline"), 0.16.into());
+        camera.insert(String::from("name"), "left \"cam\"\n\u{e9}".into());
+        let mut metadata = Metadata::new();
+        metadata.insert(String::from("camera"), MetadataValue::Object(camera));
+        metadata.insert(
+            String::from("tags"),
+            MetadataValue::Array(vec![true.into(), MetadataValue::Null, (-1.5e-3).into()]),
+        );
+
+        pxm.save_with_metadata(&path, &metadata).unwrap();
+        assert_eq!(PXM::load_with_metadata(&path), Ok((pxm, metadata)));
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let parse = |json: &str| Parser::new(json).value();
+
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(parse(&"[".repeat(100_000)).is_err());
+
+        assert_eq!(
+            parse(r#" { "a" : [1, 2e1] , "b":"\u00e9\ud83d\ude00" } "#),
+            Ok(MetadataValue::Object(
+                vec![
+                    (
+                        String::from("a"),
+                        MetadataValue::Array(vec![1.0.into(), 20.0.into()])
+                    ),
+                    (String::from("b"), "\u{e9}\u{1f600}".into()),
+                ]
+                .into_iter()
+                .collect()
+            ))
+        );
+        assert!(parse("{\"a\": 1,}").is_err());
+        assert!(parse("[1 2]").is_err());
+        assert!(parse("\"\\ud800\"").is_err());
+        assert!(parse("tru").is_err());
+    }
+}
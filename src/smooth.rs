@@ -0,0 +1,148 @@
+use crate::pfm::PFM;
+use crate::transform::PadMode;
+use std::cmp::Ordering;
+
+impl PFM {
+    /// Average each channel over the (2 * `radius` + 1)² neighbourhood of each
+    /// pixel, filling the border with `mode`.
+    pub fn box_blur(&self, radius: usize, mode: PadMode) -> Result<PFM, &'static str> {
+        let kernel = vec![1.0 / (2 * radius + 1) as f32; 2 * radius + 1];
+        self.convolve_separable(&kernel, mode)
+    }
+
+    /// Convolve each channel with a Gaussian of standard deviation `sigma`,
+    /// truncated at 3 `sigma`, filling the border with `mode`.
+    pub fn gaussian_blur(&self, sigma: f32, mode: PadMode) -> Result<PFM, &'static str> {
+        if sigma <= 0.0 || !sigma.is_finite() {
+            return Err("Sigma must be positive");
+        }
+
+        let radius = (3.0 * sigma).ceil() as usize;
+        let mut kernel: Vec<f32> = (0..=2 * radius)
+            .map(|i| {
+                let d = i as f32 - radius as f32;
+                (-d * d / (2.0 * sigma * sigma)).exp()
+            })
+            .collect();
+        let sum: f32 = kernel.iter().sum();
+        for k in kernel.iter_mut() {
+            *k /= sum;
+        }
+
+        self.convolve_separable(&kernel, mode)
+    }
+
+    /// Replace each channel by its median over the (2 * `radius` + 1)²
+    /// neighbourhood of each pixel, filling the border with `mode`. NaN values
+    /// are ignored, and pixels whose neighbourhood is all NaN stay NaN.
+    pub fn median_filter(&self, radius: usize, mode: PadMode) -> Result<PFM, &'static str> {
+        let padded = self.pad(radius, radius, radius, radius, mode)?;
+        let num_channels = self.num_channels();
+        let size = 2 * radius + 1;
+
+        let mut data = Vec::with_capacity(self.data.len());
+        let mut window = Vec::with_capacity(size * size);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                for c in 0..num_channels {
+                    window.clear();
+                    for wy in y..y + size {
+                        for wx in x..x + size {
+                            let v = padded.data[(wy * padded.width + wx) * num_channels + c];
+                            if !v.is_nan() {
+                                window.push(v);
+                            }
+                        }
+                    }
+
+                    if window.is_empty() {
+                        data.push(f32::NAN);
+                    } else {
+                        window.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                        data.push(window[window.len() / 2]);
+                    }
+                }
+            }
+        }
+
+        Ok(self.with_data(self.width, self.height, self.color, data))
+    }
+
+    /// Convolve each channel with `kernel` of odd length horizontally and then
+    /// vertically.
+    fn convolve_separable(&self, kernel: &[f32], mode: PadMode) -> Result<PFM, &'static str> {
+        let radius = kernel.len() / 2;
+        let padded = self.pad(radius, radius, radius, radius, mode)?;
+        let num_channels = self.num_channels();
+
+        // Horizontal pass, keeping the padded rows for the vertical pass.
+        let row_len = self.width * num_channels;
+        let mut horizontal = vec![0.0; padded.height * row_len];
+        for (y, row) in horizontal.chunks_exact_mut(row_len.max(1)).enumerate() {
+            let src = &padded.data[y * padded.width * num_channels..];
+            for (i, v) in row.iter_mut().enumerate() {
+                *v = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(k, w)| w * src[i + k * num_channels])
+                    .sum();
+            }
+        }
+
+        let mut data = vec![0.0; self.data.len()];
+        for (y, row) in data.chunks_exact_mut(row_len.max(1)).enumerate() {
+            for (i, v) in row.iter_mut().enumerate() {
+                *v = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(k, w)| w * horizontal[(y + k) * row_len + i])
+                    .sum();
+            }
+        }
+
+        Ok(self.with_data(self.width, self.height, self.color, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::mono;
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        assert!(a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-5));
+    }
+
+    #[test]
+    fn test_blur() {
+        let pfm = mono(3, 1, vec![0.0, 3.0, 6.0]);
+
+        let blurred = pfm.box_blur(1, PadMode::Replicate).unwrap();
+        assert_close(&blurred.data, &[1.0, 3.0, 5.0]);
+
+        // The rows above and below are zero as well.
+        let blurred = pfm.box_blur(1, PadMode::Constant(0.0)).unwrap();
+        assert_close(&blurred.data, &[1.0 / 3.0, 1.0, 1.0]);
+
+        let constant = mono(4, 4, vec![2.0; 16]);
+        let blurred = constant.gaussian_blur(1.0, PadMode::Reflect).unwrap();
+        assert_close(&blurred.data, &[2.0; 16]);
+        assert!(constant.gaussian_blur(0.0, PadMode::Reflect).is_err());
+    }
+
+    #[test]
+    fn test_median_filter() {
+        let pfm = mono(
+            3,
+            3,
+            vec![1.0, 1.0, 1.0, 1.0, 100.0, 1.0, 1.0, f32::NAN, 1.0],
+        );
+
+        let filtered = pfm.median_filter(1, PadMode::Replicate).unwrap();
+        assert!(filtered.data.iter().all(|&v| v == 1.0));
+
+        let nan = mono(1, 1, vec![f32::NAN]);
+        assert!(nan.median_filter(1, PadMode::Reflect).unwrap().data[0].is_nan());
+    }
+}
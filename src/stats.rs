@@ -0,0 +1,118 @@
+use crate::pfm::PFM;
+
+/// Summary statistics of a set of values. All fields but `count` are NaN if
+/// there are no values.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Stats {
+    /// Number of values which were taken into account.
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    /// Population standard deviation.
+    pub stddev: f32,
+}
+
+/// Statistics of an image returned by `PFM::stats`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImageStats {
+    /// Statistics over all channels.
+    pub global: Stats,
+    /// Statistics of each channel.
+    pub channels: Vec<Stats>,
+}
+
+impl PFM {
+    /// Compute the statistics of the raw pixel values, globally and per
+    /// channel. If `skip_non_finite` is true, NaN and infinite values are
+    /// ignored.
+    pub fn stats(&self, skip_non_finite: bool) -> ImageStats {
+        let num_channels = self.num_channels();
+        let global = Stats::from_values(
+            self.data
+                .iter()
+                .filter(|v| !skip_non_finite || v.is_finite())
+                .copied(),
+        );
+        let channels = (0..num_channels)
+            .map(|c| {
+                Stats::from_values(
+                    self.data
+                        .iter()
+                        .skip(c)
+                        .step_by(num_channels)
+                        .filter(|v| !skip_non_finite || v.is_finite())
+                        .copied(),
+                )
+            })
+            .collect();
+
+        ImageStats { global, channels }
+    }
+}
+
+impl Stats {
+    fn from_values(values: impl Iterator<Item = f32> + Clone) -> Stats {
+        let mut count = 0;
+        let mut min = f32::INFINITY;
+        let mut max = f32::NEG_INFINITY;
+        let mut sum = 0.0f64;
+        for v in values.clone() {
+            count += 1;
+            min = min.min(v);
+            max = max.max(v);
+            sum += v as f64;
+        }
+
+        if count == 0 {
+            return Stats {
+                count,
+                min: f32::NAN,
+                max: f32::NAN,
+                mean: f32::NAN,
+                stddev: f32::NAN,
+            };
+        }
+
+        let mean = sum / count as f64;
+        let variance = values.map(|v| (v as f64 - mean).powi(2)).sum::<f64>() / count as f64;
+
+        Stats {
+            count,
+            min,
+            max,
+            mean: mean as f32,
+            stddev: variance.sqrt() as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pfm::PFMBuilder;
+
+    #[test]
+    fn test_stats() {
+        let pfm = PFMBuilder::new()
+            .color(true)
+            .size(2, 1)
+            .data(vec![1.0, 2.0, f32::NAN, 3.0, 4.0, 5.0])
+            .build()
+            .unwrap();
+
+        let stats = pfm.stats(true);
+        assert_eq!(stats.global.count, 5);
+        assert_eq!((stats.global.min, stats.global.max), (1.0, 5.0));
+        assert_eq!(stats.global.mean, 3.0);
+        assert_eq!(stats.global.stddev, 2.0f32.sqrt());
+        assert_eq!(stats.channels.len(), 3);
+        assert_eq!(stats.channels[0].mean, 2.0);
+        assert_eq!(stats.channels[0].stddev, 1.0);
+        assert_eq!(stats.channels[2].count, 1);
+
+        let stats = pfm.stats(false);
+        assert_eq!(stats.global.count, 6);
+        assert!(stats.global.mean.is_nan());
+        assert_eq!(stats.channels[1].mean, 3.0);
+    }
+}
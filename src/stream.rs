@@ -0,0 +1,650 @@
+use crate::common::{f32_as_bytes_mut, swap_bytes, try_vec, Endian, RowOrder};
+use crate::options::{PfmReadOptions, Progress};
+use crate::pfm::{decode_header_with_comments, encode_header, encode_values, PfmHeader, PFM};
+use std::io::prelude::*;
+use std::io::{BufReader, ErrorKind, SeekFrom};
+
+/// Upper bound of the header size including comments, to avoid reading
+/// unbounded garbage.
+const MAX_HEADER_LEN: usize = 64 * 1024;
+
+/// Reads a PFM file row by row from objects implementing `Read` trait, so
+/// that only one row has to be held in memory.
+///
+/// Rows are yielded in file order, i.e. from the **bottom** row to the top
+/// row, together with their row index in the top to bottom order used by
+/// `PFM::data`.
+#[derive(Debug)]
+pub struct PfmReader<R: Read> {
+    reader: BufReader<R>,
+    header: PfmHeader,
+    comments: Vec<String>,
+    header_len: usize,
+    rows_read: usize,
+}
+
+impl<R: Read> PfmReader<R> {
+    /// Create a reader and parse the header of the PFM file.
+    pub fn new(reader: R) -> Result<PfmReader<R>, &'static str> {
+        let mut reader = BufReader::new(reader);
+        let raw = read_raw_header(&mut reader)?;
+        let (header, comments, _) = decode_header_with_comments(&raw)?;
+
+        Ok(PfmReader {
+            reader,
+            header,
+            comments,
+            header_len: raw.len(),
+            rows_read: 0,
+        })
+    }
+
+    /// Get the header of the PFM file.
+    pub fn header(&self) -> &PfmHeader {
+        &self.header
+    }
+
+    /// Get the comments of the header of the PFM file.
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// Decode the next row into `row`, which must have `header().row_len()`
+    /// elements. Returns the index of the decoded row in top to bottom order,
+    /// or `None` if all rows have been read.
+    pub fn read_row(&mut self, row: &mut [f32]) -> Result<Option<usize>, &'static str> {
+        if self.rows_read == self.header.height {
+            return Ok(None);
+        }
+
+        if row.len() != self.header.row_len() {
+            return Err("The length of row is not equal to width * channels");
+        }
+
+        if self.reader.read_exact(f32_as_bytes_mut(row)).is_err() {
+            return Err("Reached EOF before reading all rows");
+        }
+
+        if self.header.endian != Endian::host() {
+            swap_bytes(row);
+        }
+
+        self.rows_read += 1;
+
+        Ok(Some(self.header.height - self.rows_read))
+    }
+}
+
+impl<R: Read> Iterator for PfmReader<R> {
+    type Item = Result<(usize, Vec<f32>), &'static str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rows_read == self.header.height {
+            return None;
+        }
+
+        let mut row = match try_vec(self.header.row_len(), 0.0) {
+            Ok(row) => row,
+            Err(e) => {
+                self.rows_read = self.header.height;
+                return Some(Err(e));
+            }
+        };
+        match self.read_row(&mut row) {
+            Ok(Some(index)) => Some(Ok((index, row))),
+            Ok(None) => None,
+            Err(e) => {
+                // Stop iterating after the first error
+                self.rows_read = self.header.height;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Writes a PFM file row by row into objects implementing `Write` and `Seek`
+/// traits, so that the image never has to be held in memory.
+///
+/// Rows are accepted in top to bottom order. Since PFM files store rows from
+/// bottom to top, the writer seeks to the position of each row.
+#[derive(Debug)]
+pub struct PfmWriter<W: Write + Seek> {
+    writer: W,
+    header: PfmHeader,
+    payload_start: u64,
+    rows_written: usize,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write + Seek> PfmWriter<W> {
+    /// Create a writer and write the header of the PFM file.
+    pub fn new(mut writer: W, header: PfmHeader) -> Result<PfmWriter<W>, &'static str> {
+        if header.width == 0 || header.height == 0 {
+            return Err("Invalid width or height");
+        }
+
+        if header.scale_factor == 0.0 || !header.scale_factor.is_finite() {
+            return Err("Invalid scaling factor");
+        }
+
+        let row_bytes = match header.row_len().checked_mul(4) {
+            Some(n) if n.checked_mul(header.height).is_some() => n,
+            _ => return Err("Invalid width or height"),
+        };
+
+        if writer.write_all(&encode_header(&header)).is_err() {
+            return Err("Unable to write into the writer");
+        }
+
+        let payload_start = match writer.stream_position() {
+            Ok(pos) => pos,
+            Err(_) => return Err("Unable to seek in the writer"),
+        };
+
+        Ok(PfmWriter {
+            writer,
+            header,
+            payload_start,
+            rows_written: 0,
+            buffer: Vec::with_capacity(row_bytes),
+        })
+    }
+
+    /// Get the header of the PFM file.
+    pub fn header(&self) -> &PfmHeader {
+        &self.header
+    }
+
+    /// Encode and write the next row in top to bottom order. `row` must have
+    /// `header().row_len()` elements.
+    pub fn write_row(&mut self, row: &[f32]) -> Result<(), &'static str> {
+        if self.rows_written == self.header.height {
+            return Err("All rows have already been written");
+        }
+
+        if row.len() != self.header.row_len() {
+            return Err("The length of row is not equal to width * channels");
+        }
+
+        self.buffer.clear();
+        encode_values(row, self.header.endian, &mut self.buffer);
+
+        let file_row = (self.header.height - 1 - self.rows_written) as u64;
+        let offset = self.payload_start + file_row * self.buffer.len() as u64;
+        if self.writer.seek(SeekFrom::Start(offset)).is_err() {
+            return Err("Unable to seek in the writer");
+        }
+
+        if self.writer.write_all(&self.buffer).is_err() {
+            return Err("Unable to write into the writer");
+        }
+
+        self.rows_written += 1;
+
+        Ok(())
+    }
+
+    /// Check that all rows have been written, flush and return the inner
+    /// writer positioned at the end of the file.
+    pub fn finish(mut self) -> Result<W, &'static str> {
+        if self.rows_written != self.header.height {
+            return Err("Not all rows have been written");
+        }
+
+        let end = self.payload_start + (self.header.height * self.header.row_len() * 4) as u64;
+        if self.writer.seek(SeekFrom::Start(end)).is_err() {
+            return Err("Unable to seek in the writer");
+        }
+
+        if self.writer.flush().is_err() {
+            return Err("Unable to flush data");
+        }
+
+        Ok(self.writer)
+    }
+}
+
+impl PFM {
+    /// Decode only the `w` x `h` rectangle whose top left corner is at
+    /// (`x`, `y`) from a PFM file, seeking over the rest of the pixel
+    /// payload. The reader must be positioned at the start of the file.
+    pub fn read_window(
+        reader: &mut (impl Read + Seek),
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+    ) -> Result<PFM, &'static str> {
+        let raw = read_raw_header(reader)?;
+        let (header, comments, _) = decode_header_with_comments(&raw)?;
+
+        if w == 0 || h == 0 {
+            return Err("Invalid window size");
+        }
+
+        match (x.checked_add(w), y.checked_add(h)) {
+            (Some(right), Some(bottom)) if right <= header.width && bottom <= header.height => (),
+            _ => return Err("The window is out of the image bounds"),
+        }
+
+        let payload_start = match reader.stream_position() {
+            Ok(pos) => pos,
+            Err(_) => return Err("Unable to seek in the reader"),
+        };
+
+        // Grow the data as rows are read, so that a large window of a
+        // truncated file fails before allocating all of it.
+        let row_len = w * header.num_channels();
+        let mut data = Vec::new();
+        for row in y..y + h {
+            let start = data.len();
+            if data.try_reserve(row_len).is_err() {
+                return Err("Image too large");
+            }
+            data.resize(start + row_len, 0.0);
+            read_pixels_at(reader, &header, payload_start, x, row, &mut data[start..])?;
+        }
+
+        let window = PfmHeader {
+            width: w,
+            height: h,
+            ..header
+        };
+
+        let mut pfm = PFM::from_header(&window, data)?;
+        pfm.comments = comments;
+        pfm.take_unit_from_comments();
+
+        Ok(pfm)
+    }
+
+    /// Read a PFM file into `pfm`, reusing the allocation of its data when
+    /// it's large enough, e.g. when loading a sequence of same-sized frames.
+    /// Rows are read straight into `pfm.data` without an intermediate buffer,
+    /// growing it only as rows arrive, so wrap unbuffered readers in a
+    /// `BufReader`. On failure the contents of `pfm` are unspecified.
+    pub fn read_into(reader: &mut impl Read, pfm: &mut PFM) -> Result<(), &'static str> {
+        let raw = read_raw_header(reader)?;
+        let (header, comments, _) = decode_header_with_comments(&raw)?;
+        let row_len = header.row_len();
+
+        pfm.data.clear();
+        for _ in 0..header.height {
+            let start = pfm.data.len();
+            if pfm.data.try_reserve(row_len).is_err() {
+                return Err("Image too large");
+            }
+            pfm.data.resize(start + row_len, 0.0);
+
+            if reader
+                .read_exact(f32_as_bytes_mut(&mut pfm.data[start..]))
+                .is_err()
+            {
+                return Err("Reached EOF before reading all rows");
+            }
+        }
+
+        if header.endian != Endian::host() {
+            swap_bytes(&mut pfm.data);
+        }
+
+        pfm.width = header.width;
+        pfm.height = header.height;
+        pfm.color = header.color;
+        pfm.scale_factor = header.scale_factor;
+        pfm.endian = header.endian;
+        pfm.unit = None;
+        pfm.comments = comments;
+        pfm.row_order = RowOrder::BottomToTop;
+        pfm.take_unit_from_comments();
+        pfm.set_row_order(RowOrder::TopToBottom);
+
+        Ok(())
+    }
+}
+
+/// Read a PFM file row by row, averaging `n` x `n` blocks of pixels with the
+/// smallest `n` which yields at most `max_pixels` pixels. Only the reduced
+/// image and one row of block sums are held in memory.
+pub(crate) fn read_downsampled(
+    reader: &mut impl Read,
+    max_pixels: usize,
+    options: &mut PfmReadOptions,
+) -> Result<PFM, &'static str> {
+    if max_pixels == 0 {
+        return Err("Invalid pixel budget");
+    }
+
+    let mut reader = PfmReader::new(reader)?;
+    let header = *reader.header();
+    let (width, height) = (header.width, header.height);
+    options.check_size(width, height)?;
+    let num_channels = header.num_channels();
+
+    let mut factor = 1;
+    while width.div_ceil(factor) * height.div_ceil(factor) > max_pixels {
+        factor += 1;
+    }
+
+    let out_width = width.div_ceil(factor);
+    let out_height = height.div_ceil(factor);
+    let out_row_len = out_width * num_channels;
+    let mut data = vec![0.0f32; out_row_len * out_height];
+    let mut sums = vec![0.0f64; out_row_len];
+    let mut row = vec![0.0f32; header.row_len()];
+    let mut rows = 0;
+
+    loop {
+        options.check_cancelled()?;
+        let y = match reader.read_row(&mut row)? {
+            Some(y) => y,
+            None => break,
+        };
+
+        for (x, pixel) in row.chunks(num_channels).enumerate() {
+            let sum = &mut sums[x / factor * num_channels..][..num_channels];
+            for (s, &v) in sum.iter_mut().zip(pixel.iter()) {
+                *s += v as f64;
+            }
+        }
+
+        // Rows arrive bottom-up, so a block row is complete at its top row.
+        if y % factor == 0 {
+            let block_y = y / factor;
+            let block_height = factor.min(height - y);
+            let dst = &mut data[block_y * out_row_len..][..out_row_len];
+            for (i, (v, sum)) in dst.iter_mut().zip(sums.iter_mut()).enumerate() {
+                let block_width = factor.min(width - i / num_channels * factor);
+                *v = (*sum / (block_width * block_height) as f64) as f32;
+                *sum = 0.0;
+            }
+        }
+
+        rows += 1;
+        options.report(Progress {
+            bytes: reader.header_len + rows * header.row_len() * 4,
+            rows,
+            total_rows: Some(height),
+        });
+    }
+
+    let reduced = PfmHeader {
+        width: out_width,
+        height: out_height,
+        ..header
+    };
+
+    let mut pfm = PFM::from_header(&reduced, data)?;
+    pfm.comments = reader.comments;
+    pfm.take_unit_from_comments();
+
+    Ok(pfm)
+}
+
+/// Decode the pixels of row `row`, in top to bottom order, starting at column
+/// `x` straight into `dst`.
+pub(crate) fn read_pixels_at(
+    reader: &mut (impl Read + Seek),
+    header: &PfmHeader,
+    payload_start: u64,
+    x: usize,
+    row: usize,
+    dst: &mut [f32],
+) -> Result<(), &'static str> {
+    let file_row = (header.height - 1 - row) as u64;
+    let offset = (file_row * header.width as u64 + x as u64) * header.num_channels() as u64 * 4;
+    if reader
+        .seek(SeekFrom::Start(payload_start + offset))
+        .is_err()
+    {
+        return Err("Unable to seek in the reader");
+    }
+
+    if reader.read_exact(f32_as_bytes_mut(dst)).is_err() {
+        return Err("Reached EOF before reading all rows");
+    }
+
+    if header.endian != Endian::host() {
+        swap_bytes(dst);
+    }
+
+    Ok(())
+}
+
+/// Read the header bytes up to and including the single whitespace character
+/// following the scale token.
+pub(crate) fn read_raw_header(reader: &mut impl Read) -> Result<Vec<u8>, &'static str> {
+    let mut raw = Vec::new();
+    let mut num_tokens = 0;
+    let mut in_token = false;
+    let mut in_comment = false;
+    let mut byte = [0u8];
+
+    while num_tokens < 4 {
+        if raw.len() >= MAX_HEADER_LEN {
+            return Err("Header is too long");
+        }
+
+        match reader.read(&mut byte) {
+            Ok(0) => return Err("Reached EOF before finishing parsing"),
+            Ok(_) => (),
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => return Err("Unable to read from file"),
+        }
+        raw.push(byte[0]);
+
+        if in_comment {
+            in_comment = byte[0] != b'\n' && byte[0] != b'\r';
+        } else if byte[0] == b'#' && !in_token && num_tokens > 0 {
+            in_comment = true;
+        } else if byte[0].is_ascii_whitespace() {
+            if in_token {
+                num_tokens += 1;
+            }
+            in_token = false;
+        } else {
+            in_token = true;
+        }
+    }
+
+    Ok(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+    use crate::unit::Unit;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_pfm_reader() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .scale(1.0)
+            .size(2, 3)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .build()
+            .unwrap();
+        let mut buffer = Vec::new();
+        pfm.write_into(&mut buffer).unwrap();
+
+        let reader = PfmReader::new(Cursor::new(buffer)).unwrap();
+        assert_eq!(*reader.header(), pfm.header());
+
+        let rows: Vec<_> = reader.map(|r| r.unwrap()).collect();
+        assert_eq!(
+            rows,
+            vec![
+                (2, vec![5.0, 6.0]),
+                (1, vec![3.0, 4.0]),
+                (0, vec![1.0, 2.0])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pfm_reader_truncated() {
+        let mut reader = PfmReader::new(&b"Pf 1 2 -1 \0\0\0\0\0\0"[..]).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+
+        assert!(PfmReader::new(&b"Pf 1 2"[..]).is_err());
+    }
+
+    #[test]
+    fn test_pfm_reader_huge_header() {
+        let mut reader = PfmReader::new(&b"Pf 1152921504606846975 1 -1 "[..]).unwrap();
+        assert_eq!(reader.next(), Some(Err("Image too large")));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_read_raw_header_interrupted() {
+        /// Fails every other read with `ErrorKind::Interrupted`.
+        struct Flaky<'a>(&'a [u8], bool);
+
+        impl<'a> Read for Flaky<'a> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.1 = !self.1;
+                if self.1 {
+                    return Err(ErrorKind::Interrupted.into());
+                }
+                self.0.read(buf)
+            }
+        }
+
+        let raw = read_raw_header(&mut Flaky(b"Pf 1 2 -1 \0\0", false)).unwrap();
+        assert_eq!(raw, b"Pf 1 2 -1 ");
+    }
+
+    #[test]
+    fn test_read_window() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .scale(-3.0)
+            .size(3, 3)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0])
+            .build()
+            .unwrap();
+        let mut buffer = Vec::new();
+        pfm.write_into(&mut buffer).unwrap();
+
+        let window = PFM::read_window(&mut Cursor::new(&buffer), 1, 1, 2, 2).unwrap();
+        assert_eq!(window.width, 2);
+        assert_eq!(window.height, 2);
+        assert_eq!(window.scale_factor, 3.0);
+        assert_eq!(window.endian, Endian::Little);
+        assert_eq!(window.data, vec![5.0, 6.0, 8.0, 9.0]);
+
+        assert!(PFM::read_window(&mut Cursor::new(&buffer), 2, 0, 2, 1).is_err());
+    }
+
+    #[test]
+    fn test_read_window_metadata() {
+        let mut pfm = PFM::from_fn(2, 2, false, |x, y, _| (x + 10 * y) as f32);
+        pfm.comments = vec!["made by test".to_string()];
+        pfm.unit = Some(Unit::Meters);
+        let buffer = pfm.to_bytes().unwrap();
+
+        let window = PFM::read_window(&mut Cursor::new(&buffer), 0, 1, 2, 1).unwrap();
+        assert_eq!(window.comments, pfm.comments);
+        assert_eq!(window.unit, pfm.unit);
+
+        let truncated = b"Pf 100000 100000 -1 \0\0\x80\x3f";
+        let window = PFM::read_window(&mut Cursor::new(&truncated[..]), 0, 0, 100000, 100000);
+        assert_eq!(window, Err("Reached EOF before reading all rows"));
+    }
+
+    #[test]
+    fn test_read_into() {
+        let mut pfm = PFM::new_filled(2, 2, true, 1.0);
+        let capacity = pfm.data.capacity();
+
+        let mut frame = PFM::from_fn(2, 1, false, |x, _, _| x as f32);
+        frame.endian = Endian::Big;
+        let mut buffer = Vec::new();
+        frame.write_into(&mut buffer).unwrap();
+
+        PFM::read_into(&mut Cursor::new(&buffer), &mut pfm).unwrap();
+        assert_eq!(pfm, frame);
+        assert_eq!(pfm.data.capacity(), capacity);
+
+        let truncated = &buffer[..buffer.len() - 1];
+        assert!(PFM::read_into(&mut Cursor::new(truncated), &mut pfm).is_err());
+
+        let commented = b"Pf # 1 2 3\n1 1 -1 \0\0\x80\x3f";
+        PFM::read_into(&mut Cursor::new(&commented[..]), &mut pfm).unwrap();
+        assert_eq!(pfm.comments, vec!["1 2 3"]);
+        assert_eq!(pfm.data, vec![1.0]);
+    }
+
+    #[test]
+    fn test_read_into_huge_header() {
+        let mut pfm = PFM::new_filled(1, 1, false, 1.0);
+        let truncated = b"Pf 100000 100000 -1 \0\0\x80\x3f";
+        assert_eq!(
+            PFM::read_into(&mut Cursor::new(&truncated[..]), &mut pfm),
+            Err("Reached EOF before reading all rows")
+        );
+
+        let frame = PFM::from_fn(2, 3, false, |x, y, _| (x + 10 * y) as f32);
+        let buffer = frame.to_bytes().unwrap();
+        PFM::read_into(&mut Cursor::new(&buffer), &mut pfm).unwrap();
+        assert_eq!(pfm, frame);
+    }
+
+    #[test]
+    fn test_read_downsampled() {
+        let pfm = PFM::from_fn(5, 3, false, |x, y, _| (x + 10 * y) as f32);
+        let buffer = pfm.to_bytes().unwrap();
+
+        let mut options = PfmReadOptions::new().max_pixels(6);
+        let reduced = PFM::read_from_with(&mut buffer.as_slice(), &mut options).unwrap();
+        assert_eq!((reduced.width, reduced.height), (3, 2));
+        assert_eq!(reduced.data, vec![5.5, 7.5, 9.0, 20.5, 22.5, 24.0]);
+
+        let mut options = PfmReadOptions::new().max_pixels(15);
+        let full = PFM::read_from_with(&mut buffer.as_slice(), &mut options).unwrap();
+        assert_eq!(full, pfm);
+    }
+
+    #[test]
+    fn test_pfm_writer() {
+        let pfm = PFMBuilder::new()
+            .color(true)
+            .scale(-2.0)
+            .size(1, 3)
+            .data(vec![0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 1.0, 1.0, 1.0])
+            .build()
+            .unwrap();
+        let mut buffer_gt = Vec::new();
+        pfm.write_into(&mut buffer_gt).unwrap();
+
+        let mut writer = PfmWriter::new(Cursor::new(Vec::new()), pfm.header()).unwrap();
+        for row in pfm.data.chunks(3) {
+            writer.write_row(row).unwrap();
+        }
+        assert!(writer.write_row(&[0.0, 0.0, 0.0]).is_err());
+        let buffer = writer.finish().unwrap().into_inner();
+
+        assert_eq!(buffer, buffer_gt);
+    }
+
+    #[test]
+    fn test_pfm_writer_incomplete() {
+        let header = PfmHeader {
+            width: 2,
+            height: 2,
+            color: false,
+            scale_factor: 1.0,
+            endian: Endian::Big,
+        };
+
+        let mut writer = PfmWriter::new(Cursor::new(Vec::new()), header).unwrap();
+        assert!(writer.write_row(&[1.0]).is_err());
+        writer.write_row(&[1.0, 2.0]).unwrap();
+        assert!(writer.finish().is_err());
+    }
+}
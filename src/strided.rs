@@ -0,0 +1,128 @@
+use crate::common::RowOrder;
+use crate::options::PfmWriteOptions;
+use crate::pfm::{encode_rows, PfmHeader, PFM};
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+/// A borrowed image whose rows start every `stride` values, e.g. a GPU
+/// readback with a padded pitch. It's written without repacking the rows.
+#[derive(Debug, Copy, Clone)]
+pub struct StridedPfm<'a> {
+    header: PfmHeader,
+    stride: usize,
+    data: &'a [f32],
+}
+
+impl<'a> StridedPfm<'a> {
+    /// Wrap `data`, whose rows are in top to bottom order and start every
+    /// `stride` values. The padding after the last row may be omitted.
+    pub fn new(
+        header: PfmHeader,
+        stride: usize,
+        data: &'a [f32],
+    ) -> Result<StridedPfm<'a>, &'static str> {
+        if header.width == 0 || header.height == 0 {
+            return Err("Invalid width or height");
+        }
+
+        let row_len = header.row_len();
+        if stride < row_len {
+            return Err("The stride is smaller than width * channels");
+        }
+
+        let len = stride
+            .checked_mul(header.height - 1)
+            .and_then(|len| len.checked_add(row_len));
+        if len.is_none_or(|len| data.len() < len) {
+            return Err("The length of data is smaller than the strided image");
+        }
+
+        Ok(StridedPfm {
+            header,
+            stride,
+            data,
+        })
+    }
+
+    /// Get the header of the image.
+    pub fn header(&self) -> &PfmHeader {
+        &self.header
+    }
+
+    /// Number of values between the starts of consecutive rows.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Rows in top to bottom order, without the padding.
+    pub fn rows(&self) -> impl DoubleEndedIterator<Item = &'a [f32]> + '_ {
+        let row_len = self.header.row_len();
+        (0..self.header.height).map(move |y| &self.data[y * self.stride..][..row_len])
+    }
+
+    /// Copy the rows into a packed `PFM`.
+    pub fn to_pfm(&self) -> PFM {
+        PFM {
+            width: self.header.width,
+            height: self.header.height,
+            color: self.header.color,
+            scale_factor: self.header.scale_factor,
+            endian: self.header.endian,
+            data: self.rows().flatten().copied().collect(),
+            unit: None,
+            comments: Vec::new(),
+            row_order: RowOrder::TopToBottom,
+        }
+    }
+
+    /// Encode and write the image to objects implementing `Write` trait.
+    pub fn write_into(&self, writer: &mut impl Write) -> Result<(), &'static str> {
+        self.write_into_with(writer, &mut PfmWriteOptions::new())
+    }
+
+    /// Encode and write the image to objects implementing `Write` trait with
+    /// the given options.
+    pub fn write_into_with(
+        &self,
+        writer: &mut impl Write,
+        options: &mut PfmWriteOptions,
+    ) -> Result<(), &'static str> {
+        let rows: Vec<&[f32]> = self.rows().rev().collect();
+        let mut writer = BufWriter::new(writer);
+        encode_rows(&self.header, &[], &rows, &mut writer, options)?;
+
+        match writer.flush() {
+            Err(_) => Err("Unable to flush data"),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Endian;
+
+    #[test]
+    fn test_strided() {
+        let header = PfmHeader {
+            width: 2,
+            height: 2,
+            color: false,
+            scale_factor: 1.0,
+            endian: Endian::Big,
+        };
+        let data = [1.0, 2.0, -1.0, -1.0, 3.0, 4.0];
+
+        assert!(StridedPfm::new(header, 1, &data).is_err());
+        assert!(StridedPfm::new(header, 5, &data).is_err());
+
+        let strided = StridedPfm::new(header, 4, &data).unwrap();
+        let pfm = strided.to_pfm();
+        assert_eq!(pfm.data, vec![1.0, 2.0, 3.0, 4.0]);
+
+        let mut buffer = Vec::new();
+        strided.write_into(&mut buffer).unwrap();
+        assert_eq!(buffer, pfm.to_bytes().unwrap());
+    }
+}
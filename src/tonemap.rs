@@ -0,0 +1,110 @@
+use crate::pfm::PFM;
+
+/// Tone mapping operator used by `PFM::tonemap`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ToneMap {
+    /// Clamp to [0, 1] and apply the gamma correction `v^(1 / gamma)`.
+    Gamma(f32),
+    /// Global Reinhard operator, scaling the log-average luminance to `key`
+    /// (0.18 is a common choice).
+    Reinhard { key: f32 },
+}
+
+impl PFM {
+    /// Map the scaled pixel values to [0, 1] with `op`. The result has a scaling
+    /// factor of 1 and no unit.
+    pub fn tonemap(&self, op: ToneMap) -> Result<PFM, &'static str> {
+        let num_channels = self.num_channels();
+        let scaled = self.scaled_data();
+
+        let data = match op {
+            ToneMap::Gamma(gamma) => {
+                if gamma <= 0.0 || !gamma.is_finite() {
+                    return Err("Gamma must be positive");
+                }
+                scaled
+                    .iter()
+                    .map(|v| finite_or_zero(*v).clamp(0.0, 1.0).powf(1.0 / gamma))
+                    .collect()
+            }
+            ToneMap::Reinhard { key } => {
+                if key <= 0.0 || !key.is_finite() {
+                    return Err("Key must be positive");
+                }
+
+                let luminances: Vec<f32> =
+                    scaled.chunks_exact(num_channels).map(luminance).collect();
+                let log_sum: f64 = luminances.iter().map(|l| (1e-4 + *l as f64).ln()).sum();
+                let log_average = (log_sum / luminances.len().max(1) as f64).exp() as f32;
+
+                let mut data = Vec::with_capacity(scaled.len());
+                for (pixel, &l) in scaled.chunks_exact(num_channels).zip(luminances.iter()) {
+                    let l_scaled = key / log_average * l;
+                    let ratio = if l > 0.0 {
+                        l_scaled / (1.0 + l_scaled) / l
+                    } else {
+                        0.0
+                    };
+                    data.extend(
+                        pixel
+                            .iter()
+                            .map(|v| (finite_or_zero(*v) * ratio).clamp(0.0, 1.0)),
+                    );
+                }
+                data
+            }
+        };
+
+        let mut pfm = self.with_data(self.width, self.height, self.color, data);
+        pfm.scale_factor = 1.0;
+        pfm.unit = None;
+
+        Ok(pfm)
+    }
+}
+
+/// Rec. 709 luminance of a pixel, ignoring NaN and negative values.
+fn luminance(pixel: &[f32]) -> f32 {
+    let v = |c: usize| finite_or_zero(pixel[c]).max(0.0);
+    match pixel.len() {
+        3 => 0.2126 * v(0) + 0.7152 * v(1) + 0.0722 * v(2),
+        _ => v(0),
+    }
+}
+
+fn finite_or_zero(v: f32) -> f32 {
+    if v.is_nan() {
+        0.0
+    } else if v.is_infinite() {
+        v.signum() * f32::MAX
+    } else {
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+
+    #[test]
+    fn test_tonemap() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .scale(2.0)
+            .size(4, 1)
+            .data(vec![-1.0, 0.125, 0.5, f32::INFINITY])
+            .build()
+            .unwrap();
+
+        let gamma = pfm.tonemap(ToneMap::Gamma(2.0)).unwrap();
+        assert_eq!(gamma.scale_factor, 1.0);
+        assert_eq!(gamma.data, vec![0.0, 0.5, 1.0, 1.0]);
+        assert!(pfm.tonemap(ToneMap::Gamma(0.0)).is_err());
+
+        let reinhard = pfm.tonemap(ToneMap::Reinhard { key: 0.18 }).unwrap();
+        assert_eq!(reinhard.data[0], 0.0);
+        assert!(reinhard.data[1] < reinhard.data[2]);
+        assert!(reinhard.data.iter().all(|v| (0.0..=1.0).contains(v)));
+    }
+}
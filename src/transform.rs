@@ -0,0 +1,457 @@
+use crate::common::RowOrder;
+use crate::pfm::PFM;
+
+/// Sampling filter used by `PFM::resize`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+/// How `PFM::pad` fills the border.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum PadMode {
+    /// Fill all channels with a constant value.
+    Constant(f32),
+    /// Repeat the edge pixels.
+    Replicate,
+    /// Mirror the image at the edge pixels, without repeating them.
+    Reflect,
+}
+
+impl PFM {
+    /// Extract the `w` x `h` rectangle whose top left corner is at (`x`, `y`),
+    /// keeping the scaling factor, endianness and color mode.
+    pub fn crop(&self, x: usize, y: usize, w: usize, h: usize) -> Result<PFM, &'static str> {
+        if w == 0 || h == 0 {
+            return Err("Invalid crop size");
+        }
+
+        match (x.checked_add(w), y.checked_add(h)) {
+            (Some(right), Some(bottom)) if right <= self.width && bottom <= self.height => (),
+            _ => return Err("The crop rectangle is out of the image bounds"),
+        }
+
+        let num_channels = self.num_channels();
+        let mut data = Vec::with_capacity(w * h * num_channels);
+        for row in self.rows().skip(y).take(h) {
+            data.extend_from_slice(&row[x * num_channels..(x + w) * num_channels]);
+        }
+
+        Ok(self.with_data(w, h, self.color, data))
+    }
+
+    /// Flip the image upside down in place.
+    pub fn flip_vertical(&mut self) {
+        let row_len = self.width * self.num_channels();
+        if row_len == 0 {
+            return;
+        }
+
+        let mut rows = self.data.chunks_exact_mut(row_len);
+        while let (Some(top), Some(bottom)) = (rows.next(), rows.next_back()) {
+            top.swap_with_slice(bottom);
+        }
+    }
+
+    /// Reorder the rows of `data` into `order`, flipping them if needed.
+    pub fn set_row_order(&mut self, order: RowOrder) {
+        if self.row_order != order {
+            self.flip_vertical();
+            self.row_order = order;
+        }
+    }
+
+    /// Flip the image left to right in place.
+    pub fn flip_horizontal(&mut self) {
+        let num_channels = self.num_channels();
+        for row in self.rows_mut() {
+            let mut pixels = row.chunks_exact_mut(num_channels);
+            while let (Some(left), Some(right)) = (pixels.next(), pixels.next_back()) {
+                left.swap_with_slice(right);
+            }
+        }
+    }
+
+    /// Resample the image to `new_width` x `new_height` with `filter`.
+    /// Pixel centers are aligned, and samples outside the image are clamped
+    /// to the border.
+    pub fn resize(
+        &self,
+        new_width: usize,
+        new_height: usize,
+        filter: Filter,
+    ) -> Result<PFM, &'static str> {
+        if new_width == 0 || new_height == 0 {
+            return Err("Invalid width or height");
+        }
+
+        if self.width == 0 || self.height == 0 {
+            return Err("Unable to resize an empty image");
+        }
+
+        let num_channels = self.num_channels();
+        let scale_x = self.width as f32 / new_width as f32;
+        let scale_y = self.height as f32 / new_height as f32;
+        let max_x = (self.width - 1) as f32;
+        let max_y = (self.height - 1) as f32;
+        let at = |x: usize, y: usize, c: usize| self.data[(y * self.width + x) * num_channels + c];
+
+        let mut data = Vec::with_capacity(new_width * new_height * num_channels);
+        for y in 0..new_height {
+            let sy = ((y as f32 + 0.5) * scale_y - 0.5).max(0.0).min(max_y);
+            for x in 0..new_width {
+                let sx = ((x as f32 + 0.5) * scale_x - 0.5).max(0.0).min(max_x);
+                for c in 0..num_channels {
+                    let v = match filter {
+                        Filter::Nearest => at(sx.round() as usize, sy.round() as usize, c),
+                        Filter::Bilinear => {
+                            let (x0, y0) = (sx.floor() as usize, sy.floor() as usize);
+                            let (x1, y1) =
+                                ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+                            let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+                            let top = at(x0, y0, c) * (1.0 - fx) + at(x1, y0, c) * fx;
+                            let bottom = at(x0, y1, c) * (1.0 - fx) + at(x1, y1, c) * fx;
+                            top * (1.0 - fy) + bottom * fy
+                        }
+                    };
+                    data.push(v);
+                }
+            }
+        }
+
+        Ok(self.with_data(new_width, new_height, self.color, data))
+    }
+
+    /// Rotate the image by 90 degrees clockwise.
+    pub fn rotate90(&self) -> PFM {
+        self.rotate_quarter(|x, y, w, _| (y, w - 1 - x))
+    }
+
+    /// Rotate the image by 180 degrees.
+    pub fn rotate180(&self) -> PFM {
+        let mut pfm = self.flipped_vertical();
+        pfm.flip_horizontal();
+        pfm
+    }
+
+    /// Rotate the image by 270 degrees clockwise, i.e. 90 degrees
+    /// counterclockwise.
+    pub fn rotate270(&self) -> PFM {
+        self.rotate_quarter(|x, y, _, h| (h - 1 - y, x))
+    }
+
+    /// Rotate by a quarter turn, where `source` maps a pixel of the rotated
+    /// image with size (`w`, `h`) to its pixel in `self`.
+    fn rotate_quarter<F>(&self, source: F) -> PFM
+    where
+        F: Fn(usize, usize, usize, usize) -> (usize, usize),
+    {
+        let num_channels = self.num_channels();
+        let (w, h) = (self.height, self.width);
+        let mut data = Vec::with_capacity(self.data.len());
+        for y in 0..h {
+            for x in 0..w {
+                let (sx, sy) = source(x, y, w, h);
+                let offset = (sy * self.width + sx) * num_channels;
+                data.extend_from_slice(&self.data[offset..offset + num_channels]);
+            }
+        }
+
+        self.with_data(w, h, self.color, data)
+    }
+
+    /// Place `images` of the same height side by side, from left to right.
+    /// The endianness and unit are taken from the first image.
+    pub fn hconcat(images: &[&PFM]) -> Result<PFM, &'static str> {
+        let first = check_concat(images)?;
+        if images.iter().any(|pfm| pfm.height != first.height) {
+            return Err("Images must have the same height");
+        }
+
+        let width = images.iter().map(|pfm| pfm.width).sum();
+        let mut data = Vec::with_capacity(images.iter().map(|pfm| pfm.data.len()).sum());
+        for y in 0..first.height {
+            for pfm in images {
+                let row_len = pfm.width * pfm.num_channels();
+                data.extend_from_slice(&pfm.data[y * row_len..(y + 1) * row_len]);
+            }
+        }
+
+        Ok(first.with_data(width, first.height, first.color, data))
+    }
+
+    /// Stack `images` of the same width on top of each other, from top to
+    /// bottom. The endianness and unit are taken from the first image.
+    pub fn vconcat(images: &[&PFM]) -> Result<PFM, &'static str> {
+        let first = check_concat(images)?;
+        if images.iter().any(|pfm| pfm.width != first.width) {
+            return Err("Images must have the same width");
+        }
+
+        let height = images.iter().map(|pfm| pfm.height).sum();
+        let mut data = Vec::with_capacity(images.iter().map(|pfm| pfm.data.len()).sum());
+        for pfm in images {
+            data.extend_from_slice(&pfm.data);
+        }
+
+        Ok(first.with_data(first.width, height, first.color, data))
+    }
+
+    /// Add `top`, `bottom`, `left` and `right` pixels of border filled with
+    /// `mode`.
+    pub fn pad(
+        &self,
+        top: usize,
+        bottom: usize,
+        left: usize,
+        right: usize,
+        mode: PadMode,
+    ) -> Result<PFM, &'static str> {
+        let empty = self.width == 0 || self.height == 0;
+        if empty && !matches!(mode, PadMode::Constant(_)) {
+            return Err("Unable to pad an empty image from its border");
+        }
+
+        let num_channels = self.num_channels();
+        let width = self.width + left + right;
+        let height = self.height + top + bottom;
+        let mut data = Vec::with_capacity(width * height * num_channels);
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = match mode {
+                    PadMode::Constant(v) => {
+                        let inside = (left..left + self.width).contains(&x)
+                            && (top..top + self.height).contains(&y);
+                        if !inside {
+                            data.extend(std::iter::repeat_n(v, num_channels));
+                            continue;
+                        }
+                        (x - left, y - top)
+                    }
+                    PadMode::Replicate => (
+                        (x.max(left) - left).min(self.width - 1),
+                        (y.max(top) - top).min(self.height - 1),
+                    ),
+                    PadMode::Reflect => (
+                        reflect(x as isize - left as isize, self.width),
+                        reflect(y as isize - top as isize, self.height),
+                    ),
+                };
+
+                let offset = (sy * self.width + sx) * num_channels;
+                data.extend_from_slice(&self.data[offset..offset + num_channels]);
+            }
+        }
+
+        Ok(self.with_data(width, height, self.color, data))
+    }
+
+    /// Get an upside down copy of the image.
+    pub fn flipped_vertical(&self) -> PFM {
+        let mut pfm = self.clone();
+        pfm.flip_vertical();
+        pfm
+    }
+
+    /// Get a left to right flipped copy of the image.
+    pub fn flipped_horizontal(&self) -> PFM {
+        let mut pfm = self.clone();
+        pfm.flip_horizontal();
+        pfm
+    }
+}
+
+/// Map index `i` into [0, `n`) by mirroring at the first and last index.
+fn reflect(i: isize, n: usize) -> usize {
+    if n == 1 {
+        return 0;
+    }
+
+    let period = 2 * (n as isize - 1);
+    let i = i.rem_euclid(period);
+    if i < n as isize {
+        i as usize
+    } else {
+        (period - i) as usize
+    }
+}
+
+/// Check that `images` can be concatenated, and return the first image.
+fn check_concat<'a>(images: &[&'a PFM]) -> Result<&'a PFM, &'static str> {
+    let first = match images.first() {
+        Some(first) => *first,
+        None => return Err("No images to concatenate"),
+    };
+
+    for pfm in images {
+        if pfm.color != first.color {
+            return Err("Images must have the same color mode");
+        }
+        if pfm.scale_factor != first.scale_factor {
+            return Err("Images must have the same scaling factor");
+        }
+        if pfm.data.len() != pfm.width * pfm.height * pfm.num_channels() {
+            return Err("Length of data does not match the image size");
+        }
+    }
+
+    Ok(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+
+    #[test]
+    fn test_crop() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .scale(2.0)
+            .size(3, 3)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0])
+            .build()
+            .unwrap();
+
+        let cropped = pfm.crop(1, 0, 2, 2).unwrap();
+        assert_eq!((cropped.width, cropped.height), (2, 2));
+        assert_eq!(cropped.scale_factor, 2.0);
+        assert_eq!(cropped.data, vec![2.0, 3.0, 5.0, 6.0]);
+
+        assert!(pfm.crop(2, 2, 2, 1).is_err());
+        assert!(pfm.crop(0, 0, 0, 1).is_err());
+    }
+
+    #[test]
+    fn test_flip() {
+        let mut pfm = PFMBuilder::new()
+            .color(true)
+            .size(2, 3)
+            .data((0..18).map(|v| v as f32).collect())
+            .build()
+            .unwrap();
+
+        let flipped = pfm.flipped_vertical();
+        assert_eq!(&flipped.data[..6], &pfm.data[12..]);
+        assert_eq!(&flipped.data[6..12], &pfm.data[6..12]);
+        assert_eq!(flipped.flipped_vertical(), pfm);
+
+        pfm.flip_horizontal();
+        assert_eq!(&pfm.data[..6], &[3.0, 4.0, 5.0, 0.0, 1.0, 2.0]);
+        assert_eq!(pfm.flipped_horizontal(), flipped.flipped_vertical());
+    }
+
+    #[test]
+    fn test_resize() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .size(2, 2)
+            .data(vec![0.0, 1.0, 2.0, 3.0])
+            .build()
+            .unwrap();
+
+        let nearest = pfm.resize(4, 4, Filter::Nearest).unwrap();
+        assert_eq!(&nearest.data[..4], &[0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(&nearest.data[12..], &[2.0, 2.0, 3.0, 3.0]);
+
+        let bilinear = pfm.resize(4, 1, Filter::Bilinear).unwrap();
+        assert_eq!(bilinear.data, vec![1.0, 1.25, 1.75, 2.0]);
+
+        let down = pfm.resize(1, 1, Filter::Bilinear).unwrap();
+        assert_eq!(down.data, vec![1.5]);
+
+        assert!(pfm.resize(0, 1, Filter::Nearest).is_err());
+    }
+
+    #[test]
+    fn test_rotate() {
+        // 1 2 3
+        // 4 5 6
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .size(3, 2)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .build()
+            .unwrap();
+
+        let rotated = pfm.rotate90();
+        assert_eq!((rotated.width, rotated.height), (2, 3));
+        assert_eq!(rotated.data, vec![4.0, 1.0, 5.0, 2.0, 6.0, 3.0]);
+
+        let rotated = pfm.rotate270();
+        assert_eq!((rotated.width, rotated.height), (2, 3));
+        assert_eq!(rotated.data, vec![3.0, 6.0, 2.0, 5.0, 1.0, 4.0]);
+
+        assert_eq!(pfm.rotate180().data, vec![6.0, 5.0, 4.0, 3.0, 2.0, 1.0]);
+        assert_eq!(pfm.rotate90().rotate90(), pfm.rotate180());
+        assert_eq!(pfm.rotate90().rotate270(), pfm);
+
+        let color = PFMBuilder::new()
+            .color(true)
+            .size(2, 1)
+            .data(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .build()
+            .unwrap();
+        assert_eq!(color.rotate90().data, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(color.rotate270().data, vec![4.0, 5.0, 6.0, 1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_concat() {
+        // 1 2   5
+        // 3 4   6
+        let left = PFMBuilder::new()
+            .color(false)
+            .size(2, 2)
+            .data(vec![1.0, 2.0, 3.0, 4.0])
+            .build()
+            .unwrap();
+        let right = PFMBuilder::new()
+            .color(false)
+            .size(1, 2)
+            .data(vec![5.0, 6.0])
+            .build()
+            .unwrap();
+
+        let h = PFM::hconcat(&[&left, &right]).unwrap();
+        assert_eq!((h.width, h.height), (3, 2));
+        assert_eq!(h.data, vec![1.0, 2.0, 5.0, 3.0, 4.0, 6.0]);
+
+        let v = PFM::vconcat(&[&left, &left]).unwrap();
+        assert_eq!((v.width, v.height), (2, 4));
+        assert_eq!(&v.data[4..], &left.data[..]);
+
+        assert!(PFM::vconcat(&[&left, &right]).is_err());
+        assert!(PFM::hconcat(&[]).is_err());
+
+        let mut scaled = right.clone();
+        scaled.scale_factor = 2.0;
+        assert!(PFM::hconcat(&[&left, &scaled]).is_err());
+    }
+
+    #[test]
+    fn test_pad() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .size(3, 1)
+            .data(vec![1.0, 2.0, 3.0])
+            .build()
+            .unwrap();
+
+        let padded = pfm.pad(1, 0, 2, 1, PadMode::Constant(-1.0)).unwrap();
+        assert_eq!((padded.width, padded.height), (6, 2));
+        assert_eq!(&padded.data[..6], &[-1.0; 6]);
+        assert_eq!(&padded.data[6..], &[-1.0, -1.0, 1.0, 2.0, 3.0, -1.0]);
+
+        let padded = pfm.pad(0, 1, 2, 2, PadMode::Replicate).unwrap();
+        assert_eq!(&padded.data[..7], &[1.0, 1.0, 1.0, 2.0, 3.0, 3.0, 3.0]);
+        assert_eq!(&padded.data[..7], &padded.data[7..]);
+
+        let padded = pfm.pad(0, 0, 2, 3, PadMode::Reflect).unwrap();
+        assert_eq!(padded.data, vec![3.0, 2.0, 1.0, 2.0, 3.0, 2.0, 1.0, 2.0]);
+
+        let empty = PFMBuilder::new().build().unwrap();
+        assert!(empty.pad(1, 1, 1, 1, PadMode::Reflect).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use crate::metadata::parse_entry;
+use crate::pfm::PFM;
+use std::fmt;
+use std::str::FromStr;
+
+/// Key of the `key=value` header comment storing `PFM::unit`.
+const UNIT_KEY: &str = "unit";
+
+/// Physical unit of the pixel values.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Unit {
+    Meters,
+    Millimeters,
+    /// Disparity in pixels.
+    DisparityPixels,
+    /// Radiance in W/sr/m².
+    Radiance,
+}
+
+impl Unit {
+    /// Multiplicative factor converting values in `self` into values in `to`.
+    pub fn factor_to(self, to: Unit) -> Result<f32, &'static str> {
+        match (self, to) {
+            (from, to) if from == to => Ok(1.0),
+            (Unit::Meters, Unit::Millimeters) => Ok(1000.0),
+            (Unit::Millimeters, Unit::Meters) => Ok(0.001),
+            _ => Err("Incompatible units"),
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Unit::Meters => "m",
+            Unit::Millimeters => "mm",
+            Unit::DisparityPixels => "px",
+            Unit::Radiance => "W/sr/m2",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Unit {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Unit, &'static str> {
+        match s {
+            "m" => Ok(Unit::Meters),
+            "mm" => Ok(Unit::Millimeters),
+            "px" => Ok(Unit::DisparityPixels),
+            "W/sr/m2" => Ok(Unit::Radiance),
+            _ => Err("Unknown unit"),
+        }
+    }
+}
+
+/// Comments to write for an image with `unit`, i.e. `comments` with a
+/// `unit=` entry replacing any existing one. Comments are kept as they are if
+/// the unit is unknown.
+pub(crate) fn comments_with_unit(comments: &[String], unit: Option<Unit>) -> Vec<String> {
+    let mut comments = comments.to_vec();
+    if let Some(unit) = unit {
+        comments.retain(|c| parse_entry(c).is_none_or(|(key, _)| key != UNIT_KEY));
+        comments.push(format!("{}={}", UNIT_KEY, unit));
+    }
+
+    comments
+}
+
+/// Remove the first `unit=` entry from decoded `comments` and return its unit,
+/// if it's recognized.
+pub(crate) fn take_unit(comments: &mut Vec<String>) -> Option<Unit> {
+    let i = comments
+        .iter()
+        .position(|c| parse_entry(c).is_some_and(|(key, _)| key == UNIT_KEY))?;
+    let unit = parse_entry(&comments[i])?.1.parse().ok()?;
+    comments.remove(i);
+
+    Some(unit)
+}
+
+impl PFM {
+    /// Move a recognized `unit=` entry of the decoded comments into `unit`.
+    pub(crate) fn take_unit_from_comments(&mut self) {
+        if let Some(unit) = take_unit(&mut self.comments) {
+            self.unit = Some(unit);
+        }
+    }
+
+    /// Convert pixel values into unit `to`, multiplying them by the
+    /// corresponding factor. The image must have a unit.
+    pub fn convert_unit(&mut self, to: Unit) -> Result<(), &'static str> {
+        let from = match self.unit {
+            Some(unit) => unit,
+            None => return Err("The image has no unit"),
+        };
+
+        let factor = from.factor_to(to)?;
+        if factor != 1.0 {
+            for v in self.data.iter_mut() {
+                *v *= factor;
+            }
+        }
+        self.unit = Some(to);
+
+        Ok(())
+    }
+
+    /// Convert a monochrome disparity map into a depth map in meters with
+    /// `depth = focal_px * baseline_m / (disparity + doffs)`, where `doffs` is
+    /// the x-difference of the principal points. Non-finite disparities
+    /// become NaN, and disparities with `disparity + doffs <= 0` become
+    /// infinitely far away. The image must have no unit or
+    /// `Unit::DisparityPixels`.
+    pub fn disparity_to_depth(
+        &self,
+        focal_px: f32,
+        baseline_m: f32,
+        doffs: f32,
+    ) -> Result<PFM, &'static str> {
+        if self.color {
+            return Err("Only monochrome images can be converted to depth");
+        }
+
+        match self.unit {
+            None | Some(Unit::DisparityPixels) => (),
+            _ => return Err("The image is not a disparity map"),
+        }
+
+        let data = self
+            .scaled_data()
+            .iter()
+            .map(|&d| {
+                if !d.is_finite() {
+                    f32::NAN
+                } else if d + doffs <= 0.0 {
+                    f32::INFINITY
+                } else {
+                    focal_px * baseline_m / (d + doffs)
+                }
+            })
+            .collect();
+
+        let mut pfm = self.with_data(self.width, self.height, false, data);
+        pfm.scale_factor = 1.0;
+        pfm.unit = Some(Unit::Meters);
+
+        Ok(pfm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfm::PFMBuilder;
+
+    #[test]
+    fn test_convert_unit() {
+        let mut pfm = PFMBuilder::new()
+            .color(false)
+            .size(2, 1)
+            .unit(Unit::Meters)
+            .data(vec![1.0, 2.5])
+            .build()
+            .unwrap();
+
+        pfm.convert_unit(Unit::Millimeters).unwrap();
+        assert_eq!(pfm.unit, Some(Unit::Millimeters));
+        assert_eq!(pfm.data, vec![1000.0, 2500.0]);
+
+        assert!(pfm.convert_unit(Unit::DisparityPixels).is_err());
+        assert_eq!(pfm.data, vec![1000.0, 2500.0]);
+
+        pfm.unit = None;
+        assert!(pfm.convert_unit(Unit::Meters).is_err());
+    }
+
+    #[test]
+    fn test_disparity_to_depth() {
+        let pfm = PFMBuilder::new()
+            .color(false)
+            .size(4, 1)
+            .unit(Unit::DisparityPixels)
+            .data(vec![10.0, 0.0, f32::INFINITY, -1.0])
+            .build()
+            .unwrap();
+
+        let depth = pfm.disparity_to_depth(100.0, 0.5, 0.0).unwrap();
+        assert_eq!(depth.unit, Some(Unit::Meters));
+        assert_eq!(depth.data[0], 5.0);
+        assert_eq!(depth.data[1], f32::INFINITY);
+        assert!(depth.data[2].is_nan());
+        assert_eq!(depth.data[3], f32::INFINITY);
+
+        let depth = pfm.disparity_to_depth(100.0, 0.5, 15.0).unwrap();
+        assert_eq!(depth.data[0], 2.0);
+
+        assert!(depth.disparity_to_depth(100.0, 0.5, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_unit_round_trip() {
+        let mut pfm = PFMBuilder::new()
+            .color(false)
+            .size(1, 1)
+            .unit(Unit::Millimeters)
+            .comments(vec![String::from("unit=m"), String::from("note")])
+            .data(vec![1.0])
+            .build()
+            .unwrap();
+
+        let decoded = PFM::from_bytes(&pfm.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.unit, Some(Unit::Millimeters));
+        assert_eq!(decoded.comments, vec!["note"]);
+
+        pfm.unit = None;
+        pfm.comments = vec![String::from("unit=furlong")];
+        let decoded = PFM::from_bytes(&pfm.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.unit, None);
+        assert_eq!(decoded.comments, vec!["unit=furlong"]);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::pfm::{data_len, PFM};
+
+/// Result of `PFM::validate`, describing the consistency of a `PFM`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ValidationReport {
+    /// Number of values expected from width * height * channels, or `None`
+    /// if it overflows.
+    pub expected_len: Option<usize>,
+    /// Number of values actually stored in `data`.
+    pub actual_len: usize,
+    /// True if the scaling factor is finite and non-zero.
+    pub scale_valid: bool,
+    /// Number of pixels with at least one NaN channel.
+    pub nan_pixels: usize,
+    /// Number of pixels with at least one infinite channel.
+    pub inf_pixels: usize,
+}
+
+impl ValidationReport {
+    /// True if the dimensions, data length and scaling factor are consistent,
+    /// i.e. the image can be encoded.
+    pub fn is_valid(&self) -> bool {
+        self.expected_len
+            .is_some_and(|len| len > 0 && len == self.actual_len)
+            && self.scale_valid
+    }
+
+    /// True if the image is valid and contains no NaN or infinite pixels.
+    pub fn is_clean(&self) -> bool {
+        self.is_valid() && self.nan_pixels == 0 && self.inf_pixels == 0
+    }
+}
+
+impl PFM {
+    /// Check the consistency of the image, and count NaN and infinite pixels.
+    pub fn validate(&self) -> ValidationReport {
+        let num_channels = if self.color { 3 } else { 1 };
+        let mut nan_pixels = 0;
+        let mut inf_pixels = 0;
+
+        for pixel in self.data.chunks(num_channels) {
+            if pixel.iter().any(|v| v.is_nan()) {
+                nan_pixels += 1;
+            }
+            if pixel.iter().any(|v| v.is_infinite()) {
+                inf_pixels += 1;
+            }
+        }
+
+        ValidationReport {
+            expected_len: data_len(self.width, self.height, num_channels),
+            actual_len: self.data.len(),
+            scale_valid: self.scale_factor.is_finite() && self.scale_factor != 0.0,
+            nan_pixels,
+            inf_pixels,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pfm::PFMBuilder;
+
+    #[test]
+    fn test_validate() {
+        let mut pfm = PFMBuilder::new()
+            .color(true)
+            .size(2, 1)
+            .data(vec![0.0, f32::NAN, f32::INFINITY, 1.0, 1.0, 1.0])
+            .build()
+            .unwrap();
+
+        let report = pfm.validate();
+        assert!(report.is_valid());
+        assert!(!report.is_clean());
+        assert_eq!(report.nan_pixels, 1);
+        assert_eq!(report.inf_pixels, 1);
+
+        pfm.data.pop();
+        pfm.scale_factor = f32::INFINITY;
+        let report = pfm.validate();
+        assert_eq!(report.expected_len, Some(6));
+        assert_eq!(report.actual_len, 5);
+        assert!(!report.scale_valid);
+        assert!(!report.is_valid());
+
+        pfm.width = usize::MAX;
+        pfm.height = 2;
+        let report = pfm.validate();
+        assert_eq!(report.expected_len, None);
+        assert!(!report.is_valid());
+    }
+}